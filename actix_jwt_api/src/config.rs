@@ -0,0 +1,459 @@
+use clap::Parser;
+use serde::Deserialize;
+use std::fmt;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Settings are layered: defaults < TOML file < environment variables < CLI flags.
+// Environment variables and CLI flags are both handled by clap (`env = ...`),
+// which already gives CLI flags precedence over the environment.
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const MIN_JWT_SECRET_LEN: usize = 32;
+
+// Wrapper for values that must never end up in logs
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+impl Secret {
+    pub fn new(value: &str) -> Self {
+        Secret(value.to_string())
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StorageBackend {
+    Postgres,
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseSettings {
+    pub url: Secret,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    // None disables closing idle connections
+    pub idle_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct JwtSettings {
+    pub secret: Secret,
+    pub expiration_hours: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    // host:port as configured; hostnames such as localhost:8080 are allowed
+    pub bind_address: String,
+    pub workers: Option<usize>,
+    pub log_level: String,
+    // None when storage_backend = "memory" (dev mode, no Postgres)
+    pub database: Option<DatabaseSettings>,
+    pub jwt: JwtSettings,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    File(String),
+    Missing(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File(msg) => write!(f, "Config file error: {}", msg),
+            ConfigError::Missing(msg) => write!(f, "Missing required setting: {}", msg),
+            ConfigError::Invalid(msg) => write!(f, "Invalid setting: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "Secure Notes API")]
+struct Cli {
+    /// Path to a TOML config file (defaults to ./config.toml if present)
+    #[arg(long, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+
+    /// Address to listen on, e.g. 127.0.0.1:8080
+    #[arg(long, env = "BIND_ADDRESS")]
+    bind_address: Option<String>,
+
+    /// Number of HTTP worker threads
+    #[arg(long, env = "WORKERS")]
+    workers: Option<usize>,
+
+    /// Log filter, e.g. info or actix_web=debug
+    #[arg(long, env = "LOG_LEVEL")]
+    log_level: Option<String>,
+
+    /// postgres or memory
+    #[arg(long, env = "STORAGE_BACKEND")]
+    storage_backend: Option<String>,
+
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+
+    #[arg(long, env = "DATABASE_MAX_CONNECTIONS")]
+    database_max_connections: Option<u32>,
+
+    #[arg(long, env = "DATABASE_MIN_CONNECTIONS")]
+    database_min_connections: Option<u32>,
+
+    /// Seconds to wait for a free pool connection
+    #[arg(long, env = "DATABASE_ACQUIRE_TIMEOUT_SECS")]
+    database_acquire_timeout_secs: Option<u64>,
+
+    /// Seconds before an idle pool connection is closed (0 = never)
+    #[arg(long, env = "DATABASE_IDLE_TIMEOUT_SECS")]
+    database_idle_timeout_secs: Option<u64>,
+
+    #[arg(long, env = "JWT_SECRET", hide_env_values = true)]
+    jwt_secret: Option<String>,
+
+    #[arg(long, env = "JWT_EXPIRATION_HOURS")]
+    jwt_expiration_hours: Option<i64>,
+}
+
+// Mirrors the TOML layout; every field is optional so the file can be partial
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSettings {
+    bind_address: Option<String>,
+    workers: Option<usize>,
+    log_level: Option<String>,
+    storage_backend: Option<String>,
+    database: FileDatabaseSettings,
+    jwt: FileJwtSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileDatabaseSettings {
+    url: Option<Secret>,
+    max_connections: Option<u32>,
+    min_connections: Option<u32>,
+    acquire_timeout_secs: Option<u64>,
+    idle_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileJwtSettings {
+    secret: Option<Secret>,
+    expiration_hours: Option<i64>,
+}
+
+impl Settings {
+    pub fn load() -> Result<Self, ConfigError> {
+        let cli = Cli::parse();
+        let file = load_file(cli.config.as_ref())?;
+        Self::merge(cli, file)
+    }
+
+    fn merge(cli: Cli, file: FileSettings) -> Result<Self, ConfigError> {
+        let bind_address = cli
+            .bind_address
+            .or(file.bind_address)
+            .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+        // Resolved once here so a bad address fails at startup with a clear
+        // message; the server resolves it again when binding
+        if bind_address.to_socket_addrs().is_err() {
+            return Err(ConfigError::Invalid(format!(
+                "bind_address '{}' is not a resolvable host:port",
+                bind_address
+            )));
+        }
+
+        let workers = cli.workers.or(file.workers);
+        if workers == Some(0) {
+            return Err(ConfigError::Invalid(
+                "workers must be at least 1".to_string(),
+            ));
+        }
+
+        let log_level = cli
+            .log_level
+            .or(file.log_level)
+            .unwrap_or_else(|| "info".to_string());
+
+        let storage_backend = match cli
+            .storage_backend
+            .or(file.storage_backend)
+            .as_deref()
+            .unwrap_or("postgres")
+        {
+            "postgres" => StorageBackend::Postgres,
+            "memory" => StorageBackend::Memory,
+            other => {
+                return Err(ConfigError::Invalid(format!(
+                    "storage_backend '{}', expected 'postgres' or 'memory'",
+                    other
+                )));
+            }
+        };
+
+        let database = match storage_backend {
+            StorageBackend::Memory => None,
+            StorageBackend::Postgres => {
+                let url = cli
+                    .database_url
+                    .map(Secret)
+                    .or(file.database.url)
+                    .ok_or_else(|| {
+                        ConfigError::Missing("database.url (DATABASE_URL)".to_string())
+                    })?;
+                let max_connections = cli
+                    .database_max_connections
+                    .or(file.database.max_connections)
+                    .unwrap_or(10);
+                let min_connections = cli
+                    .database_min_connections
+                    .or(file.database.min_connections)
+                    .unwrap_or(0);
+
+                if max_connections == 0 {
+                    return Err(ConfigError::Invalid(
+                        "database.max_connections must be at least 1".to_string(),
+                    ));
+                }
+                if min_connections > max_connections {
+                    return Err(ConfigError::Invalid(
+                        "database.min_connections cannot exceed database.max_connections"
+                            .to_string(),
+                    ));
+                }
+                let acquire_timeout_secs = cli
+                    .database_acquire_timeout_secs
+                    .or(file.database.acquire_timeout_secs)
+                    .unwrap_or(5);
+                if acquire_timeout_secs == 0 {
+                    return Err(ConfigError::Invalid(
+                        "database.acquire_timeout_secs must be at least 1".to_string(),
+                    ));
+                }
+                let idle_timeout_secs = cli
+                    .database_idle_timeout_secs
+                    .or(file.database.idle_timeout_secs)
+                    .unwrap_or(600);
+
+                Some(DatabaseSettings {
+                    url,
+                    max_connections,
+                    min_connections,
+                    acquire_timeout: Duration::from_secs(acquire_timeout_secs),
+                    idle_timeout: (idle_timeout_secs > 0)
+                        .then(|| Duration::from_secs(idle_timeout_secs)),
+                })
+            }
+        };
+
+        let secret = cli
+            .jwt_secret
+            .map(Secret)
+            .or(file.jwt.secret)
+            .ok_or_else(|| ConfigError::Missing("jwt.secret (JWT_SECRET)".to_string()))?;
+        if secret.expose().len() < MIN_JWT_SECRET_LEN {
+            return Err(ConfigError::Invalid(format!(
+                "jwt.secret must be at least {} characters",
+                MIN_JWT_SECRET_LEN
+            )));
+        }
+
+        let expiration_hours = cli
+            .jwt_expiration_hours
+            .or(file.jwt.expiration_hours)
+            .unwrap_or(24);
+        if expiration_hours <= 0 {
+            return Err(ConfigError::Invalid(
+                "jwt.expiration_hours must be positive".to_string(),
+            ));
+        }
+
+        Ok(Settings {
+            bind_address,
+            workers,
+            log_level,
+            database,
+            jwt: JwtSettings {
+                secret,
+                expiration_hours,
+            },
+        })
+    }
+}
+
+// An explicitly requested file must exist; the default one is optional
+fn load_file(path: Option<&PathBuf>) -> Result<FileSettings, ConfigError> {
+    let (path, required) = match path {
+        Some(path) => (path.clone(), true),
+        None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(FileSettings::default());
+        }
+        Err(e) => return Err(ConfigError::File(format!("{}: {}", path.display(), e))),
+    };
+
+    toml::from_str(&contents).map_err(|e| ConfigError::File(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn file(toml: &str) -> FileSettings {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn minimal_file() -> FileSettings {
+        file(&format!(
+            "[database]\nurl = \"postgres://localhost/notes\"\n[jwt]\nsecret = \"{}\"",
+            SECRET
+        ))
+    }
+
+    fn invalid(cli: Cli, file: FileSettings) -> String {
+        match Settings::merge(cli, file) {
+            Err(ConfigError::Invalid(msg)) => msg,
+            other => panic!("expected an invalid setting, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults_apply_when_nothing_is_set() {
+        let settings = Settings::merge(Cli::default(), minimal_file()).unwrap();
+        assert_eq!(settings.bind_address, "127.0.0.1:8080");
+        assert_eq!(settings.workers, None);
+        assert_eq!(settings.log_level, "info");
+        assert_eq!(settings.jwt.expiration_hours, 24);
+
+        let database = settings.database.unwrap();
+        assert_eq!(database.max_connections, 10);
+        assert_eq!(database.idle_timeout, Some(Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn cli_overrides_file() {
+        let mut file = minimal_file();
+        file.workers = Some(2);
+        file.log_level = Some("warn".to_string());
+        let cli = Cli {
+            workers: Some(4),
+            ..Cli::default()
+        };
+
+        let settings = Settings::merge(cli, file).unwrap();
+        assert_eq!(settings.workers, Some(4));
+        assert_eq!(settings.log_level, "warn");
+    }
+
+    #[test]
+    fn expiration_from_cli_or_env_replaces_the_file_value() {
+        // JWT_EXPIRATION_HOURS and --jwt-expiration-hours both end up in
+        // this field
+        let mut file = minimal_file();
+        file.jwt.expiration_hours = Some(48);
+        assert_eq!(
+            Settings::merge(Cli::default(), file)
+                .unwrap()
+                .jwt
+                .expiration_hours,
+            48
+        );
+
+        let mut file = minimal_file();
+        file.jwt.expiration_hours = Some(48);
+        let cli = Cli {
+            jwt_expiration_hours: Some(6),
+            ..Cli::default()
+        };
+        assert_eq!(Settings::merge(cli, file).unwrap().jwt.expiration_hours, 6);
+    }
+
+    #[test]
+    fn hostnames_are_accepted_as_bind_address() {
+        let cli = Cli {
+            bind_address: Some("localhost:8080".to_string()),
+            ..Cli::default()
+        };
+        let settings = Settings::merge(cli, minimal_file()).unwrap();
+        assert_eq!(settings.bind_address, "localhost:8080");
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let cli = Cli {
+            bind_address: Some("localhost".to_string()),
+            ..Cli::default()
+        };
+        assert!(invalid(cli, minimal_file()).contains("bind_address"));
+
+        let cli = Cli {
+            workers: Some(0),
+            ..Cli::default()
+        };
+        assert!(invalid(cli, minimal_file()).contains("workers"));
+
+        let cli = Cli {
+            jwt_secret: Some("short".to_string()),
+            ..Cli::default()
+        };
+        assert!(invalid(cli, minimal_file()).contains("jwt.secret"));
+
+        let mut file = minimal_file();
+        file.database.min_connections = Some(20);
+        assert!(invalid(Cli::default(), file).contains("min_connections"));
+
+        let mut file = minimal_file();
+        file.storage_backend = Some("sqlite".to_string());
+        assert!(invalid(Cli::default(), file).contains("storage_backend"));
+    }
+
+    #[test]
+    fn required_settings_must_be_present() {
+        let result = Settings::merge(Cli::default(), FileSettings::default());
+        assert!(matches!(result, Err(ConfigError::Missing(_))));
+    }
+
+    #[test]
+    fn memory_backend_needs_no_database() {
+        let cli = Cli {
+            storage_backend: Some("memory".to_string()),
+            jwt_secret: Some(SECRET.to_string()),
+            ..Cli::default()
+        };
+        let settings = Settings::merge(cli, FileSettings::default()).unwrap();
+        assert!(settings.database.is_none());
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        assert!(toml::from_str::<FileSettings>("bind = \"0.0.0.0:80\"").is_err());
+    }
+}
@@ -1,14 +1,19 @@
+use actix_web::http::header::{self, Header as _};
 use actix_web::{HttpRequest, HttpResponse, Result, web};
 use bcrypt::{DEFAULT_COST, hash, verify};
 use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
 use serde::Serialize;
-use sqlx::PgPool;
-use std::env;
+use std::time::Duration;
 use uuid::Uuid;
 
-use crate::database::*;
+use crate::config::JwtSettings;
 use crate::errors::AppError;
-use crate::models::{AuthRequest, Claims, NoteRequest};
+use crate::markdown;
+use crate::models::{
+    AuthRequest, Claims, HealthResponse, NoteFormatQuery, NoteRequest, NoteResponse,
+    ShareLinkRequest, ShareLinkResponse,
+};
+use crate::repository::{HealthRepository, NoteRepository, ShareLinkRepository, UserRepository};
 
 #[derive(Serialize)]
 pub struct AuthResponse {
@@ -21,13 +26,9 @@ pub struct MessageResponse {
     pub message: String,
 }
 
-fn get_jwt_secret() -> String {
-    env::var("JWT_SECRET").unwrap_or_else(|_| "mysecretkey".to_string())
-}
-
-fn create_jwt_token(user_id: &Uuid) -> Result<String, AppError> {
+fn create_jwt_token(jwt: &JwtSettings, user_id: &Uuid) -> Result<String, AppError> {
     let expiration = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(24))
+        .checked_add_signed(chrono::Duration::hours(jwt.expiration_hours))
         .expect("valid timestamp")
         .timestamp() as usize;
 
@@ -39,7 +40,7 @@ fn create_jwt_token(user_id: &Uuid) -> Result<String, AppError> {
     let token = encode(
         &Header::default(),
         &claims,
-        &EncodingKey::from_secret(get_jwt_secret().as_ref()),
+        &EncodingKey::from_secret(jwt.secret.expose().as_ref()),
     )
     .map_err(|e| AppError::InternalError(format!("Token creation failed: {}", e)))?;
 
@@ -47,6 +48,10 @@ fn create_jwt_token(user_id: &Uuid) -> Result<String, AppError> {
 }
 
 fn extract_user_id_from_token(req: &HttpRequest) -> Result<Uuid, AppError> {
+    let jwt = req
+        .app_data::<web::Data<JwtSettings>>()
+        .ok_or_else(|| AppError::InternalError("JWT settings not configured".to_string()))?;
+
     let auth_header = req
         .headers()
         .get("Authorization")
@@ -60,7 +65,7 @@ fn extract_user_id_from_token(req: &HttpRequest) -> Result<Uuid, AppError> {
 
     let token_data = decode::<Claims>(
         token,
-        &DecodingKey::from_secret(get_jwt_secret().as_ref()),
+        &DecodingKey::from_secret(jwt.secret.expose().as_ref()),
         &Validation::default(),
     )
     .map_err(|_| AppError::Unauthorized("Invalid token".to_string()))?;
@@ -71,20 +76,42 @@ fn extract_user_id_from_token(req: &HttpRequest) -> Result<Uuid, AppError> {
     Ok(user_id)
 }
 
+// ?format= takes precedence over the Accept header; JSON is the default
+fn wants_html(format: Option<&str>, req: &HttpRequest) -> Result<bool, AppError> {
+    match format {
+        Some("html") => return Ok(true),
+        Some("json") => return Ok(false),
+        Some(other) => {
+            return Err(AppError::BadRequest(format!(
+                "Unsupported format '{}', expected 'json' or 'html'",
+                other
+            )));
+        }
+        None => {}
+    }
+
+    let preferred = header::Accept::parse(req)
+        .ok()
+        .and_then(|accept| accept.ranked().into_iter().next());
+
+    Ok(matches!(preferred, Some(mime) if mime.type_() == "text" && mime.subtype() == "html"))
+}
+
 pub async fn register(
-    pool: web::Data<PgPool>,
+    users: web::Data<dyn UserRepository>,
+    jwt: web::Data<JwtSettings>,
     req: web::Json<AuthRequest>,
 ) -> Result<HttpResponse, AppError> {
-    if let Some(_) = get_user_by_email(&pool, &req.email).await? {
+    if users.get_user_by_email(&req.email).await?.is_some() {
         return Err(AppError::Conflict("User already exists".to_string()));
     }
 
     let password_hash = hash(&req.password, DEFAULT_COST)
         .map_err(|e| AppError::InternalError(format!("Password hashing failed: {}", e)))?;
 
-    let user = create_user(&pool, &req.email, &password_hash).await?;
+    let user = users.create_user(&req.email, &password_hash).await?;
 
-    let token = create_jwt_token(&user.id)?;
+    let token = create_jwt_token(&jwt, &user.id)?;
 
     let response = AuthResponse {
         token,
@@ -95,10 +122,12 @@ pub async fn register(
 }
 
 pub async fn login(
-    pool: web::Data<PgPool>,
+    users: web::Data<dyn UserRepository>,
+    jwt: web::Data<JwtSettings>,
     req: web::Json<AuthRequest>,
 ) -> Result<HttpResponse, AppError> {
-    let user = get_user_by_email(&pool, &req.email)
+    let user = users
+        .get_user_by_email(&req.email)
         .await?
         .ok_or_else(|| AppError::Unauthorized("Invalid credentials".to_string()))?;
 
@@ -109,7 +138,7 @@ pub async fn login(
         return Err(AppError::Unauthorized("Invalid credentilas".to_string()));
     }
 
-    let token = create_jwt_token(&user.id)?;
+    let token = create_jwt_token(&jwt, &user.id)?;
 
     let response = AuthResponse {
         token,
@@ -120,41 +149,55 @@ pub async fn login(
 }
 
 pub async fn create_note_handler(
-    pool: web::Data<PgPool>,
+    notes: web::Data<dyn NoteRepository>,
     req: web::Json<NoteRequest>,
     http_req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
     let user_id = extract_user_id_from_token(&http_req)?;
-    let note = create_note(&pool, user_id, &req).await?;
+    let note = notes.create_note(user_id, &req).await?;
     Ok(HttpResponse::Created().json(note))
 }
 
 pub async fn get_notes_handler(
-    pool: web::Data<PgPool>,
+    notes: web::Data<dyn NoteRepository>,
     http_req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
     let user_id = extract_user_id_from_token(&http_req)?;
-    let notes = get_user_notes(&pool, user_id).await?;
-    Ok(HttpResponse::Ok().json(notes))
+    let user_notes = notes.get_user_notes(user_id).await?;
+    Ok(HttpResponse::Ok().json(user_notes))
 }
 
 pub async fn get_note_handler(
-    pool: web::Data<PgPool>,
+    notes: web::Data<dyn NoteRepository>,
     path: web::Path<Uuid>,
+    query: web::Query<NoteFormatQuery>,
     http_req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
     let note_id = path.into_inner();
     let user_id = extract_user_id_from_token(&http_req)?;
+    let as_html = wants_html(query.format.as_deref(), &http_req)?;
 
-    let note = get_note(&pool, note_id, user_id)
+    let note = notes
+        .get_note(note_id, user_id)
         .await?
         .ok_or_else(|| AppError::NotFound("Note not found".to_string()))?;
 
-    Ok(HttpResponse::Ok().json(note))
+    if as_html {
+        return Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(markdown::render_note_html(&note.title, &note.content)));
+    }
+
+    let response = NoteResponse {
+        metadata: markdown::extract_metadata(&note.content),
+        note,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
 }
 
 pub async fn update_note_handler(
-    pool: web::Data<PgPool>,
+    notes: web::Data<dyn NoteRepository>,
     path: web::Path<Uuid>,
     req: web::Json<NoteRequest>,
     http_req: HttpRequest,
@@ -162,7 +205,8 @@ pub async fn update_note_handler(
     let note_id = path.into_inner();
     let user_id = extract_user_id_from_token(&http_req)?;
 
-    let note = update_note(&pool, note_id, user_id, &req)
+    let note = notes
+        .update_note(note_id, user_id, &req)
         .await?
         .ok_or_else(|| AppError::NotFound("Note not found".to_string()))?;
 
@@ -170,13 +214,13 @@ pub async fn update_note_handler(
 }
 
 pub async fn delete_note_handler(
-    pool: web::Data<PgPool>,
+    notes: web::Data<dyn NoteRepository>,
     path: web::Path<Uuid>,
     http_req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
     let note_id = path.into_inner();
     let user_id = extract_user_id_from_token(&http_req)?;
-    let deleted = delete_note(&pool, note_id, user_id).await?;
+    let deleted = notes.delete_note(note_id, user_id).await?;
 
     if !deleted {
         return Err(AppError::NotFound("Note not found".to_string()));
@@ -189,9 +233,181 @@ pub async fn delete_note_handler(
     Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn health_check() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "ok",
-        "message": "Notes API is running"
-    }))
+// Share links are only managed by the note's owner
+async fn ensure_note_owner(
+    notes: &dyn NoteRepository,
+    note_id: Uuid,
+    user_id: Uuid,
+) -> Result<(), AppError> {
+    notes
+        .get_note(note_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Note not found".to_string()))?;
+
+    Ok(())
+}
+
+pub async fn create_share_link_handler(
+    notes: web::Data<dyn NoteRepository>,
+    share_links: web::Data<dyn ShareLinkRepository>,
+    path: web::Path<Uuid>,
+    req: web::Json<ShareLinkRequest>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let note_id = path.into_inner();
+    let user_id = extract_user_id_from_token(&http_req)?;
+    ensure_note_owner(notes.get_ref(), note_id, user_id).await?;
+
+    if let Some(expires_at) = req.expires_at
+        && expires_at <= chrono::Utc::now()
+    {
+        return Err(AppError::BadRequest(
+            "expires_at must be in the future".to_string(),
+        ));
+    }
+
+    let password_hash = match req.password.as_deref() {
+        Some("") => return Err(AppError::BadRequest("Password cannot be empty".to_string())),
+        Some(password) => Some(
+            hash(password, DEFAULT_COST)
+                .map_err(|e| AppError::InternalError(format!("Password hashing failed: {}", e)))?,
+        ),
+        None => None,
+    };
+
+    // Two v4 UUIDs give 244 random bits, so slugs can't be guessed or enumerated
+    let slug = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+
+    let link = share_links
+        .create_share_link(note_id, &slug, password_hash.as_deref(), req.expires_at)
+        .await?;
+
+    Ok(HttpResponse::Created().json(ShareLinkResponse::from(link)))
+}
+
+pub async fn get_share_links_handler(
+    notes: web::Data<dyn NoteRepository>,
+    share_links: web::Data<dyn ShareLinkRepository>,
+    path: web::Path<Uuid>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let note_id = path.into_inner();
+    let user_id = extract_user_id_from_token(&http_req)?;
+    ensure_note_owner(notes.get_ref(), note_id, user_id).await?;
+
+    let links: Vec<ShareLinkResponse> = share_links
+        .get_note_share_links(note_id)
+        .await?
+        .into_iter()
+        .map(ShareLinkResponse::from)
+        .collect();
+
+    Ok(HttpResponse::Ok().json(links))
+}
+
+pub async fn delete_share_link_handler(
+    notes: web::Data<dyn NoteRepository>,
+    share_links: web::Data<dyn ShareLinkRepository>,
+    path: web::Path<(Uuid, Uuid)>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let (note_id, link_id) = path.into_inner();
+    let user_id = extract_user_id_from_token(&http_req)?;
+    ensure_note_owner(notes.get_ref(), note_id, user_id).await?;
+
+    if !share_links.delete_share_link(link_id, note_id).await? {
+        return Err(AppError::NotFound("Share link not found".to_string()));
+    }
+
+    let response = MessageResponse {
+        message: "Share link revoked successfully".to_string(),
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Public, unauthenticated view of a shared note.
+// Password-protected links expect the password in the X-Share-Password header.
+pub async fn view_shared_note_handler(
+    share_links: web::Data<dyn ShareLinkRepository>,
+    path: web::Path<String>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let slug = path.into_inner();
+
+    let (link, note) = share_links
+        .get_shared_note(&slug)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Share link not found".to_string()))?;
+
+    if link.is_expired() {
+        return Err(AppError::NotFound("Share link has expired".to_string()));
+    }
+
+    if let Some(password_hash) = &link.password_hash {
+        let password = http_req
+            .headers()
+            .get("X-Share-Password")
+            .and_then(|value| value.to_str().ok())
+            .ok_or_else(|| AppError::Unauthorized("Password required".to_string()))?;
+
+        let is_valid = verify(password, password_hash)
+            .map_err(|e| AppError::InternalError(format!("Password verification failed: {}", e)))?;
+
+        if !is_valid {
+            return Err(AppError::Unauthorized("Invalid password".to_string()));
+        }
+    }
+
+    share_links.record_share_view(link.id).await?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(markdown::render_note_html(&note.title, &note.content)))
+}
+
+const READY_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Liveness: the process is up and the storage backend has not been shut down
+pub async fn health_live(health: web::Data<dyn HealthRepository>) -> HttpResponse {
+    if health.is_closed() {
+        return HttpResponse::ServiceUnavailable().json(HealthResponse {
+            status: "degraded",
+            database: "closed",
+            error: Some("Connection pool is closed".to_string()),
+            pool: None,
+        });
+    }
+
+    HttpResponse::Ok().json(HealthResponse {
+        status: "ok",
+        database: "not_checked",
+        error: None,
+        pool: None,
+    })
+}
+
+// Readiness: the database answers SELECT 1 within READY_CHECK_TIMEOUT
+pub async fn health_ready(health: web::Data<dyn HealthRepository>) -> HttpResponse {
+    let error = match tokio::time::timeout(READY_CHECK_TIMEOUT, health.ping()).await {
+        Ok(Ok(())) => None,
+        Ok(Err(e)) => Some(e.to_string()),
+        Err(_) => Some(format!(
+            "Database check timed out after {}s",
+            READY_CHECK_TIMEOUT.as_secs()
+        )),
+    };
+
+    let response = HealthResponse {
+        status: if error.is_none() { "ok" } else { "degraded" },
+        database: if error.is_none() { "up" } else { "down" },
+        error,
+        pool: health.pool_stats(),
+    };
+
+    if response.error.is_some() {
+        HttpResponse::ServiceUnavailable().json(response)
+    } else {
+        HttpResponse::Ok().json(response)
+    }
 }
@@ -1,35 +1,293 @@
+mod config;
 mod database;
 mod errors;
 mod handlers;
+mod markdown;
+mod memory;
 mod models;
+mod repository;
 
 use actix_web::{App, HttpServer, middleware::Logger, web};
+use config::Settings;
 use handlers::*;
+use repository::{
+    HealthRepository, NoteRepository, Repository, ShareLinkRepository, UserRepository,
+};
+use std::sync::Arc;
+
+// Every route of the API, shared with the tests
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/auth/register", web::post().to(register))
+        .route("/auth/login", web::post().to(login))
+        .route("/notes", web::post().to(create_note_handler))
+        .route("/notes", web::get().to(get_notes_handler))
+        .route("/notes/{id}", web::get().to(get_note_handler))
+        .route("/notes/{id}", web::put().to(update_note_handler))
+        .route("/notes/{id}", web::delete().to(delete_note_handler))
+        .route(
+            "/notes/{id}/shares",
+            web::post().to(create_share_link_handler),
+        )
+        .route("/notes/{id}/shares", web::get().to(get_share_links_handler))
+        .route(
+            "/notes/{id}/shares/{share_id}",
+            web::delete().to(delete_share_link_handler),
+        )
+        .route("/s/{slug}", web::get().to(view_shared_note_handler))
+        .route("/health/live", web::get().to(health_live))
+        .route("/health/ready", web::get().to(health_ready));
+}
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let settings = match Settings::load() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
 
-    let pool = database::create_pool()
-        .await
-        .expect("Failed to create database pool");
+    env_logger::Builder::new()
+        .parse_filters(&settings.log_level)
+        .init();
+
+    // Secrets are redacted by their Debug impl
+    println!("Loaded settings: {:#?}", settings);
+
+    // storage_backend = "memory" runs the API without Postgres (dev mode)
+    let repo: Arc<dyn Repository> = match &settings.database {
+        None => {
+            println!("Using in-memory storage (dev mode, data is not persisted)");
+            Arc::new(memory::InMemoryRepository::new())
+        }
+        Some(database_settings) => {
+            let pool = database::create_pool(database_settings)
+                .await
+                .expect("Failed to create database pool");
 
-    println!("Database connected successfully");
+            println!("Database connected successfully");
 
-    HttpServer::new(move || {
+            Arc::new(database::PgRepository::new(pool))
+        }
+    };
+
+    let users: Arc<dyn UserRepository> = repo.clone();
+    let notes: Arc<dyn NoteRepository> = repo.clone();
+    let share_links: Arc<dyn ShareLinkRepository> = repo.clone();
+    let health: Arc<dyn HealthRepository> = repo;
+
+    let jwt_settings = web::Data::new(settings.jwt.clone());
+
+    let mut server = HttpServer::new(move || {
         App::new()
-            .app_data(web::Data::new(pool.clone()))
+            .app_data(jwt_settings.clone())
+            .app_data(web::Data::from(users.clone()))
+            .app_data(web::Data::from(notes.clone()))
+            .app_data(web::Data::from(share_links.clone()))
+            .app_data(web::Data::from(health.clone()))
             .wrap(Logger::default())
-            .route("/auth/register", web::post().to(register))
-            .route("/auth/login", web::post().to(login))
-            .route("/notes", web::post().to(create_note_handler))
-            .route("/notes", web::get().to(get_notes_handler))
-            .route("/notes/{id}", web::get().to(get_note_handler))
-            .route("/notes/{id}", web::put().to(update_note_handler))
-            .route("/notes/{id}", web::delete().to(delete_note_handler))
-            .route("/health", web::get().to(health_check))
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+            .configure(routes)
+    });
+
+    if let Some(workers) = settings.workers {
+        server = server.workers(workers);
+    }
+
+    server.bind(settings.bind_address)?.run().await
+}
+
+// Handler tests run against InMemoryRepository, wired up the same way main
+// does, so they need no database
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_http::Request;
+    use actix_web::dev::{Service, ServiceResponse};
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service, read_body_json};
+    use config::{JwtSettings, Secret};
+    use serde_json::{Value, json};
+
+    async fn app() -> impl Service<Request, Response = ServiceResponse, Error = actix_web::Error> {
+        let repo: Arc<dyn Repository> = Arc::new(memory::InMemoryRepository::new());
+        let users: Arc<dyn UserRepository> = repo.clone();
+        let notes: Arc<dyn NoteRepository> = repo.clone();
+        let share_links: Arc<dyn ShareLinkRepository> = repo.clone();
+        let health: Arc<dyn HealthRepository> = repo;
+
+        init_service(
+            App::new()
+                .app_data(web::Data::new(JwtSettings {
+                    secret: Secret::new("test-secret-that-is-at-least-32-chars"),
+                    expiration_hours: 1,
+                }))
+                .app_data(web::Data::from(users))
+                .app_data(web::Data::from(notes))
+                .app_data(web::Data::from(share_links))
+                .app_data(web::Data::from(health))
+                .configure(routes),
+        )
+        .await
+    }
+
+    async fn send(
+        app: &impl Service<Request, Response = ServiceResponse, Error = actix_web::Error>,
+        req: TestRequest,
+    ) -> (StatusCode, Value) {
+        let res = call_service(app, req.to_request()).await;
+        let status = res.status();
+        (status, read_body_json(res).await)
+    }
+
+    fn authorized(req: TestRequest, token: &str) -> TestRequest {
+        req.insert_header(("Authorization", format!("Bearer {}", token)))
+    }
+
+    fn credentials(email: &str) -> Value {
+        json!({ "email": email, "password": "password123" })
+    }
+
+    #[actix_web::test]
+    async fn register_login_and_manage_notes() {
+        let app = app().await;
+
+        let (status, _) = send(
+            &app,
+            TestRequest::post()
+                .uri("/auth/register")
+                .set_json(credentials("a@example.com")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        let (status, body) = send(
+            &app,
+            TestRequest::post()
+                .uri("/auth/login")
+                .set_json(credentials("a@example.com")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        let token = body["token"].as_str().unwrap().to_string();
+
+        let (status, note) = send(
+            &app,
+            authorized(TestRequest::post().uri("/notes"), &token)
+                .set_json(json!({ "title": "Groceries", "content": "- milk" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let id = note["id"].as_str().unwrap().to_string();
+
+        let (status, list) = send(&app, authorized(TestRequest::get().uri("/notes"), &token)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(list.as_array().unwrap().len(), 1);
+        assert_eq!(list[0]["title"], "Groceries");
+
+        let (status, updated) = send(
+            &app,
+            authorized(TestRequest::put().uri(&format!("/notes/{}", id)), &token)
+                .set_json(json!({ "title": "Shopping", "content": "- milk\n- eggs" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["title"], "Shopping");
+
+        let (status, fetched) = send(
+            &app,
+            authorized(TestRequest::get().uri(&format!("/notes/{}", id)), &token),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(fetched["content"], "- milk\n- eggs");
+
+        let (status, _) = send(
+            &app,
+            authorized(TestRequest::delete().uri(&format!("/notes/{}", id)), &token),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = send(
+            &app,
+            authorized(TestRequest::get().uri(&format!("/notes/{}", id)), &token),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[actix_web::test]
+    async fn wrong_password_is_rejected() {
+        let app = app().await;
+        send(
+            &app,
+            TestRequest::post()
+                .uri("/auth/register")
+                .set_json(credentials("a@example.com")),
+        )
+        .await;
+
+        let (status, _) = send(
+            &app,
+            TestRequest::post()
+                .uri("/auth/login")
+                .set_json(json!({ "email": "a@example.com", "password": "wrong-password" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[actix_web::test]
+    async fn notes_are_private_to_their_owner() {
+        let app = app().await;
+
+        let mut tokens = Vec::new();
+        for email in ["owner@example.com", "other@example.com"] {
+            let (_, body) = send(
+                &app,
+                TestRequest::post()
+                    .uri("/auth/register")
+                    .set_json(credentials(email)),
+            )
+            .await;
+            tokens.push(body["token"].as_str().unwrap().to_string());
+        }
+        let (owner, other) = (&tokens[0], &tokens[1]);
+
+        let (_, note) = send(
+            &app,
+            authorized(TestRequest::post().uri("/notes"), owner)
+                .set_json(json!({ "title": "Private", "content": "secret" })),
+        )
+        .await;
+        let uri = format!("/notes/{}", note["id"].as_str().unwrap());
+
+        let (status, _) = send(&app, authorized(TestRequest::get().uri(&uri), other)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(
+            &app,
+            authorized(TestRequest::put().uri(&uri), other)
+                .set_json(json!({ "title": "Mine now", "content": "" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (status, _) = send(&app, authorized(TestRequest::delete().uri(&uri), other)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let (_, list) = send(&app, authorized(TestRequest::get().uri("/notes"), other)).await;
+        assert_eq!(list, json!([]));
+
+        let (status, _) = send(&app, authorized(TestRequest::get().uri(&uri), owner)).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn notes_require_a_token() {
+        let app = app().await;
+        let (status, _) = send(&app, TestRequest::get().uri("/notes")).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+    }
 }
@@ -0,0 +1,154 @@
+use pulldown_cmark::{Event, Parser, Tag, TagEnd, html};
+
+use crate::models::{Heading, NoteMetadata};
+
+// Note content is CommonMark. Raw HTML inside the markdown is passed through by
+// the renderer, so the output is always run through ammonia before it is served.
+pub fn render_html(content: &str) -> String {
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, Parser::new(content));
+
+    ammonia::clean(&unsafe_html)
+}
+
+// Renders a full note (escaped title + sanitized body) for text/html responses
+pub fn render_note_html(title: &str, content: &str) -> String {
+    format!(
+        "<article>\n<h1>{}</h1>\n{}</article>\n",
+        ammonia::clean_text(title),
+        render_html(content)
+    )
+}
+
+pub fn extract_metadata(content: &str) -> NoteMetadata {
+    let mut headings = Vec::new();
+    let mut links = Vec::new();
+    // All text of the note, with blocks separated by whitespace. Words are
+    // counted over this rather than per text event, since inline markup such
+    // as `foo**bar**` splits one word into several events.
+    let mut text = String::new();
+
+    // Text of the heading currently being parsed, if any
+    let mut current_heading: Option<(u8, String)> = None;
+
+    for event in Parser::new(content) {
+        match &event {
+            Event::Start(tag) if !is_inline(tag.to_end()) => text.push(' '),
+            Event::End(tag) if !is_inline(*tag) => text.push(' '),
+            _ => {}
+        }
+
+        match event {
+            Event::Start(Tag::Heading { level, .. }) => {
+                current_heading = Some((level as u8, String::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, text)) = current_heading.take() {
+                    headings.push(Heading {
+                        level,
+                        text: text.trim().to_string(),
+                    });
+                }
+            }
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                links.push(dest_url.to_string());
+            }
+            Event::Text(fragment) | Event::Code(fragment) => {
+                text.push_str(&fragment);
+                if let Some((_, heading_text)) = current_heading.as_mut() {
+                    heading_text.push_str(&fragment);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                text.push(' ');
+                if let Some((_, heading_text)) = current_heading.as_mut() {
+                    heading_text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    NoteMetadata {
+        headings,
+        links,
+        word_count: text.split_whitespace().count(),
+    }
+}
+
+// Inline markup continues the surrounding text; every other tag starts or
+// ends a block
+fn is_inline(tag: TagEnd) -> bool {
+    matches!(
+        tag,
+        TagEnd::Emphasis | TagEnd::Strong | TagEnd::Strikethrough | TagEnd::Link | TagEnd::Image
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_tags_are_removed() {
+        let html = render_html("Hello\n\n<script>alert('x')</script>\n\nworld");
+        assert!(!html.contains("<script"), "{}", html);
+        assert!(!html.contains("alert"), "{}", html);
+        assert!(html.contains("Hello") && html.contains("world"));
+    }
+
+    #[test]
+    fn javascript_links_are_removed() {
+        let html =
+            render_html("[click](javascript:alert(1)) and <a href=\"javascript:alert(2)\">me</a>");
+        assert!(!html.contains("javascript:"), "{}", html);
+        assert!(html.contains("click"));
+    }
+
+    #[test]
+    fn event_handler_attributes_are_removed() {
+        let html = render_html("<img src=\"x.png\" onerror=\"alert(1)\">");
+        assert!(!html.contains("onerror"), "{}", html);
+        assert!(html.contains("x.png"), "{}", html);
+    }
+
+    #[test]
+    fn note_title_is_escaped() {
+        let html = render_note_html("<b>Title</b>", "body");
+        assert!(!html.contains("<b>"), "{}", html);
+        assert!(html.contains("&lt;b&gt;Title"), "{}", html);
+    }
+
+    #[test]
+    fn headings_and_links_are_extracted() {
+        let metadata = extract_metadata(
+            "# Title with `code`\n\nSee [docs](https://example.com).\n\n## Second\nline",
+        );
+
+        let headings: Vec<_> = metadata
+            .headings
+            .iter()
+            .map(|h| (h.level, h.text.as_str()))
+            .collect();
+        assert_eq!(headings, vec![(1, "Title with code"), (2, "Second")]);
+        assert_eq!(metadata.links, vec!["https://example.com"]);
+    }
+
+    #[test]
+    fn words_are_counted_across_blocks() {
+        let metadata = extract_metadata(
+            "# One two\n\nthree *four* five\n\n- six\n- seven\n\n```\neight nine\n```",
+        );
+        assert_eq!(metadata.word_count, 9);
+    }
+
+    #[test]
+    fn inline_markup_does_not_split_words() {
+        assert_eq!(extract_metadata("foo**bar**").word_count, 1);
+        assert_eq!(
+            extract_metadata("un_believ_able and [li](x)nk").word_count,
+            3
+        );
+        assert_eq!(extract_metadata("").word_count, 0);
+    }
+}
@@ -0,0 +1,213 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::models::PoolStats;
+use crate::models::{Note, NoteRequest, ShareLink, User};
+use crate::repository::{HealthRepository, NoteRepository, ShareLinkRepository, UserRepository};
+
+// In-memory storage used in dev mode (no Postgres required).
+// Data lives for the lifetime of the process only.
+#[derive(Default)]
+pub struct InMemoryRepository {
+    users: Mutex<HashMap<Uuid, User>>,
+    notes: Mutex<HashMap<Uuid, Note>>,
+    share_links: Mutex<HashMap<Uuid, ShareLink>>,
+}
+
+impl InMemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UserRepository for InMemoryRepository {
+    async fn create_user(&self, email: &str, password_hash: &str) -> Result<User, AppError> {
+        let mut users = self.users.lock().unwrap();
+
+        // Mirrors the UNIQUE constraint on users.email
+        if users.values().any(|u| u.email == email) {
+            return Err(AppError::Conflict("User already exists".to_string()));
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            email: email.to_string(),
+            password_hash: password_hash.to_string(),
+        };
+        users.insert(user.id, user.clone());
+
+        Ok(user)
+    }
+
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, AppError> {
+        let users = self.users.lock().unwrap();
+        Ok(users.values().find(|u| u.email == email).cloned())
+    }
+}
+
+#[async_trait]
+impl NoteRepository for InMemoryRepository {
+    async fn create_note(&self, user_id: Uuid, req: &NoteRequest) -> Result<Note, AppError> {
+        let note = Note {
+            id: Uuid::new_v4(),
+            user_id,
+            title: req.title.clone(),
+            content: req.content.clone(),
+        };
+        self.notes.lock().unwrap().insert(note.id, note.clone());
+
+        Ok(note)
+    }
+
+    async fn get_user_notes(&self, user_id: Uuid) -> Result<Vec<Note>, AppError> {
+        let notes = self.notes.lock().unwrap();
+        let mut user_notes: Vec<Note> = notes
+            .values()
+            .filter(|n| n.user_id == user_id)
+            .cloned()
+            .collect();
+        user_notes.sort_by(|a, b| a.title.cmp(&b.title));
+
+        Ok(user_notes)
+    }
+
+    async fn get_note(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, AppError> {
+        let notes = self.notes.lock().unwrap();
+        Ok(notes
+            .get(&note_id)
+            .filter(|n| n.user_id == user_id)
+            .cloned())
+    }
+
+    async fn update_note(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        req: &NoteRequest,
+    ) -> Result<Option<Note>, AppError> {
+        let mut notes = self.notes.lock().unwrap();
+
+        match notes.get_mut(&note_id) {
+            Some(note) if note.user_id == user_id => {
+                note.title = req.title.clone();
+                note.content = req.content.clone();
+                Ok(Some(note.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    async fn delete_note(&self, note_id: Uuid, user_id: Uuid) -> Result<bool, AppError> {
+        let mut notes = self.notes.lock().unwrap();
+
+        match notes.get(&note_id) {
+            Some(note) if note.user_id == user_id => {
+                notes.remove(&note_id);
+                // Mirrors ON DELETE CASCADE on share_links.note_id
+                self.share_links
+                    .lock()
+                    .unwrap()
+                    .retain(|_, link| link.note_id != note_id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl ShareLinkRepository for InMemoryRepository {
+    async fn create_share_link(
+        &self,
+        note_id: Uuid,
+        slug: &str,
+        password_hash: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<ShareLink, AppError> {
+        let link = ShareLink {
+            id: Uuid::new_v4(),
+            note_id,
+            slug: slug.to_string(),
+            password_hash: password_hash.map(str::to_string),
+            expires_at,
+            view_count: 0,
+            created_at: Utc::now(),
+        };
+        self.share_links
+            .lock()
+            .unwrap()
+            .insert(link.id, link.clone());
+
+        Ok(link)
+    }
+
+    async fn get_note_share_links(&self, note_id: Uuid) -> Result<Vec<ShareLink>, AppError> {
+        let links = self.share_links.lock().unwrap();
+        let mut note_links: Vec<ShareLink> = links
+            .values()
+            .filter(|l| l.note_id == note_id)
+            .cloned()
+            .collect();
+        note_links.sort_by_key(|l| std::cmp::Reverse(l.created_at));
+
+        Ok(note_links)
+    }
+
+    async fn get_shared_note(&self, slug: &str) -> Result<Option<(ShareLink, Note)>, AppError> {
+        let link = match self
+            .share_links
+            .lock()
+            .unwrap()
+            .values()
+            .find(|l| l.slug == slug)
+            .cloned()
+        {
+            Some(link) => link,
+            None => return Ok(None),
+        };
+
+        let note = self.notes.lock().unwrap().get(&link.note_id).cloned();
+
+        Ok(note.map(|note| (link, note)))
+    }
+
+    async fn record_share_view(&self, link_id: Uuid) -> Result<(), AppError> {
+        if let Some(link) = self.share_links.lock().unwrap().get_mut(&link_id) {
+            link.view_count += 1;
+        }
+
+        Ok(())
+    }
+
+    async fn delete_share_link(&self, link_id: Uuid, note_id: Uuid) -> Result<bool, AppError> {
+        let mut links = self.share_links.lock().unwrap();
+
+        match links.get(&link_id) {
+            Some(link) if link.note_id == note_id => {
+                links.remove(&link_id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl HealthRepository for InMemoryRepository {
+    async fn ping(&self) -> Result<(), AppError> {
+        Ok(())
+    }
+
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
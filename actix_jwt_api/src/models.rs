@@ -1,16 +1,17 @@
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 
 // Database models
-#[derive(Debug, Serialize, FromRow)]
+#[derive(Debug, Clone, Serialize, FromRow)]
 pub struct User {
     pub id: Uuid,
     pub email: String,
     pub password_hash: String,
 }
 
-#[derive(Debug, Serialize, FromRow)]
+#[derive(Debug, Clone, Serialize, FromRow)]
 pub struct Note {
     pub id: Uuid,
     pub user_id: Uuid,
@@ -18,6 +19,91 @@ pub struct Note {
     pub content: String,
 }
 
+#[derive(Debug, Clone, FromRow)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub note_id: Uuid,
+    pub slug: String,
+    pub password_hash: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub view_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ShareLink {
+    pub fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= Utc::now())
+    }
+}
+
+// Response models
+#[derive(Debug, Serialize)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NoteMetadata {
+    pub headings: Vec<Heading>,
+    pub links: Vec<String>,
+    pub word_count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct NoteResponse {
+    #[serde(flatten)]
+    pub note: Note,
+    pub metadata: NoteMetadata,
+}
+
+// Never exposes the password hash, only whether one is set
+#[derive(Debug, Serialize)]
+pub struct ShareLinkResponse {
+    pub id: Uuid,
+    pub note_id: Uuid,
+    pub slug: String,
+    pub url: String,
+    pub has_password: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub expired: bool,
+    pub view_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<ShareLink> for ShareLinkResponse {
+    fn from(link: ShareLink) -> Self {
+        ShareLinkResponse {
+            id: link.id,
+            note_id: link.note_id,
+            url: format!("/s/{}", link.slug),
+            has_password: link.password_hash.is_some(),
+            expired: link.is_expired(),
+            slug: link.slug,
+            expires_at: link.expires_at,
+            view_count: link.view_count,
+            created_at: link.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+    pub database: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolStats>,
+}
+
 // Request models
 #[derive(Deserialize)]
 pub struct AuthRequest {
@@ -31,6 +117,18 @@ pub struct NoteRequest {
     pub content: String,
 }
 
+#[derive(Deserialize)]
+pub struct ShareLinkRequest {
+    pub expires_at: Option<DateTime<Utc>>,
+    pub password: Option<String>,
+}
+
+// Query string for GET /notes/{id}: ?format=json|html
+#[derive(Deserialize)]
+pub struct NoteFormatQuery {
+    pub format: Option<String>,
+}
+
 // JWT Claims (required for JWT to work)
 #[derive(Serialize, Deserialize)]
 pub struct Claims {
@@ -0,0 +1,65 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::errors::AppError;
+use crate::models::{Note, NoteRequest, PoolStats, ShareLink, User};
+
+// Storage abstraction so handlers don't depend on PgPool directly.
+// Implemented by PgRepository (database.rs) and InMemoryRepository (memory.rs).
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn create_user(&self, email: &str, password_hash: &str) -> Result<User, AppError>;
+    async fn get_user_by_email(&self, email: &str) -> Result<Option<User>, AppError>;
+}
+
+#[async_trait]
+pub trait NoteRepository: Send + Sync {
+    async fn create_note(&self, user_id: Uuid, req: &NoteRequest) -> Result<Note, AppError>;
+    async fn get_user_notes(&self, user_id: Uuid) -> Result<Vec<Note>, AppError>;
+    async fn get_note(&self, note_id: Uuid, user_id: Uuid) -> Result<Option<Note>, AppError>;
+    async fn update_note(
+        &self,
+        note_id: Uuid,
+        user_id: Uuid,
+        req: &NoteRequest,
+    ) -> Result<Option<Note>, AppError>;
+    async fn delete_note(&self, note_id: Uuid, user_id: Uuid) -> Result<bool, AppError>;
+}
+
+#[async_trait]
+pub trait ShareLinkRepository: Send + Sync {
+    async fn create_share_link(
+        &self,
+        note_id: Uuid,
+        slug: &str,
+        password_hash: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<ShareLink, AppError>;
+    async fn get_note_share_links(&self, note_id: Uuid) -> Result<Vec<ShareLink>, AppError>;
+    // Looks up a link and the note it points to, regardless of the note's owner
+    async fn get_shared_note(&self, slug: &str) -> Result<Option<(ShareLink, Note)>, AppError>;
+    async fn record_share_view(&self, link_id: Uuid) -> Result<(), AppError>;
+    async fn delete_share_link(&self, link_id: Uuid, note_id: Uuid) -> Result<bool, AppError>;
+}
+
+#[async_trait]
+pub trait HealthRepository: Send + Sync {
+    // Round-trips to the backing store (SELECT 1 for Postgres)
+    async fn ping(&self) -> Result<(), AppError>;
+    fn is_closed(&self) -> bool;
+    // None for backends without a connection pool
+    fn pool_stats(&self) -> Option<PoolStats>;
+}
+
+// Everything a storage backend provides, so main can pick one at runtime
+// and hand out each capability as its own web::Data<dyn ...>.
+pub trait Repository:
+    UserRepository + NoteRepository + ShareLinkRepository + HealthRepository
+{
+}
+
+impl<T> Repository for T where
+    T: UserRepository + NoteRepository + ShareLinkRepository + HealthRepository
+{
+}
@@ -9,7 +9,7 @@ echo "=== Testing Notes API (Full Functionality) ==="
 echo
 
 echo "1. Health Check:"
-curl -s $BASE_URL/health | jq '.' 2>/dev/null || curl -s $BASE_URL/health
+curl -s $BASE_URL/health/ready | jq '.' 2>/dev/null || curl -s $BASE_URL/health/ready
 echo
 
 echo "2. Register New User:"
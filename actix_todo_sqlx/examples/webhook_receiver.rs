@@ -0,0 +1,110 @@
+// A local webhook receiver for trying out and testing webhooks:
+//
+//   WEBHOOK_SECRET=whsec_... cargo run --example webhook_receiver
+//
+// It checks the X-Webhook-Signature of each request and prints the event.
+// RECEIVER_STATUS=500 makes it fail every request, to watch the retries and
+// the dead-letter state. RECEIVER_ADDRESS defaults to 127.0.0.1:9000.
+
+use actix_web::http::StatusCode;
+use actix_web::{App, HttpRequest, HttpResponse, HttpServer, web};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+// Requests signed longer ago than this are rejected as replays
+const TOLERANCE_SECS: i64 = 300;
+
+struct Receiver {
+    secret: Option<String>,
+    status: StatusCode,
+}
+
+// Checks "t=<timestamp>,v1=<hex HMAC-SHA256 of '<timestamp>.<body>'>"
+fn verify(secret: &str, header: &str, body: &[u8]) -> Result<(), String> {
+    let mut timestamp = None;
+    let mut signature = None;
+    for part in header.split(',') {
+        match part.split_once('=') {
+            Some(("t", value)) => timestamp = value.parse::<i64>().ok(),
+            Some(("v1", value)) => signature = hex::decode(value).ok(),
+            _ => {}
+        }
+    }
+    let timestamp = timestamp.ok_or("missing timestamp")?;
+    let signature = signature.ok_or("missing v1 signature")?;
+
+    if (chrono::Utc::now().timestamp() - timestamp).abs() > TOLERANCE_SECS {
+        return Err("timestamp outside the tolerance".to_string());
+    }
+
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| e.to_string())?;
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    mac.verify_slice(&signature)
+        .map_err(|_| "signature mismatch".to_string())
+}
+
+async fn receive(
+    receiver: web::Data<Receiver>,
+    req: HttpRequest,
+    body: web::Bytes,
+) -> HttpResponse {
+    let header = |name: &str| {
+        req.headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("")
+            .to_string()
+    };
+
+    let verified = match &receiver.secret {
+        Some(secret) => match verify(secret, &header("X-Webhook-Signature"), &body) {
+            Ok(()) => "signature ok".to_string(),
+            Err(e) => {
+                println!("rejected delivery {}: {}", header("X-Webhook-Delivery"), e);
+                return HttpResponse::Unauthorized().finish();
+            }
+        },
+        None => "signature not checked".to_string(),
+    };
+
+    println!(
+        "delivery {} {} ({}): {}",
+        header("X-Webhook-Delivery"),
+        header("X-Webhook-Event"),
+        verified,
+        String::from_utf8_lossy(&body)
+    );
+
+    HttpResponse::build(receiver.status).finish()
+}
+
+#[actix_web::main]
+async fn main() -> std::io::Result<()> {
+    let address =
+        std::env::var("RECEIVER_ADDRESS").unwrap_or_else(|_| "127.0.0.1:9000".to_string());
+    let status = std::env::var("RECEIVER_STATUS")
+        .ok()
+        .and_then(|status| status.parse::<u16>().ok())
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::NO_CONTENT);
+
+    let receiver = web::Data::new(Receiver {
+        secret: std::env::var("WEBHOOK_SECRET").ok(),
+        status,
+    });
+    if receiver.secret.is_none() {
+        println!("WEBHOOK_SECRET is not set; signatures will not be checked");
+    }
+    println!("Listening on http://{} (answering {})", address, status);
+
+    HttpServer::new(move || {
+        App::new()
+            .app_data(receiver.clone())
+            .default_service(web::post().to(receive))
+    })
+    .bind(address)?
+    .run()
+    .await
+}
@@ -0,0 +1,52 @@
+use actix_web::{HttpRequest, web};
+use jsonwebtoken::{DecodingKey, EncodingKey, Header, Validation, decode, encode};
+use uuid::Uuid;
+
+use crate::config::AuthSettings;
+use crate::errors::AppError;
+use crate::models::Claims;
+
+pub fn create_token(auth: &AuthSettings, user_id: Uuid) -> Result<String, AppError> {
+    let expiration = chrono::Utc::now()
+        .checked_add_signed(chrono::Duration::hours(auth.token_expiration_hours))
+        .ok_or_else(|| AppError::InternalError("Token expiration out of range".to_string()))?
+        .timestamp() as usize;
+
+    let claims = Claims {
+        user_id: user_id.to_string(),
+        exp: expiration,
+    };
+
+    encode(
+        &Header::default(),
+        &claims,
+        &EncodingKey::from_secret(auth.jwt_secret.expose().as_ref()),
+    )
+    .map_err(|e| AppError::InternalError(format!("Token creation failed: {}", e)))
+}
+
+// The caller's user id, taken from the `Authorization: Bearer <token>` header
+pub fn user_id(req: &HttpRequest) -> Result<Uuid, AppError> {
+    let auth = req
+        .app_data::<web::Data<AuthSettings>>()
+        .ok_or_else(|| AppError::InternalError("Auth settings not configured".to_string()))?;
+
+    let token = req
+        .headers()
+        .get("Authorization")
+        .ok_or_else(|| AppError::Unauthorized("Missing Authorization header".to_string()))?
+        .to_str()
+        .ok()
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or_else(|| AppError::Unauthorized("Expected a Bearer token".to_string()))?;
+
+    let token_data = decode::<Claims>(
+        token,
+        &DecodingKey::from_secret(auth.jwt_secret.expose().as_ref()),
+        &Validation::default(),
+    )
+    .map_err(|_| AppError::Unauthorized("Invalid or expired token".to_string()))?;
+
+    Uuid::parse_str(&token_data.claims.user_id)
+        .map_err(|_| AppError::Unauthorized("Invalid user ID in token".to_string()))
+}
@@ -0,0 +1,212 @@
+use actix_web::ResponseError;
+use sqlx::{Acquire, PgConnection, PgPool};
+
+use crate::database;
+use crate::errors::AppError;
+use crate::models::{
+    BulkAction, BulkItemResult, BulkMode, BulkOperation, BulkRequest, BulkResponse, CreateTodo,
+    Todo, TodoListQuery, UpdateTodo,
+};
+use crate::recurrence::Recurrence;
+use uuid::Uuid;
+
+pub const MAX_BULK_ITEMS: usize = 1000;
+
+// A single change, whether listed explicitly or produced by filter + action
+enum Step<'a> {
+    Create(&'a CreateTodo),
+    Update(i32, &'a UpdateTodo),
+    Delete(i32),
+}
+
+impl Step<'_> {
+    fn name(&self) -> &'static str {
+        match self {
+            Step::Create(_) => "create",
+            Step::Update(..) => "update",
+            Step::Delete(_) => "delete",
+        }
+    }
+
+    fn id(&self) -> Option<i32> {
+        match self {
+            Step::Create(_) => None,
+            Step::Update(id, _) | Step::Delete(id) => Some(*id),
+        }
+    }
+}
+
+fn parse_recurrence(rule: Option<&str>) -> Result<Option<Recurrence>, AppError> {
+    rule.map(str::parse::<Recurrence>).transpose()
+}
+
+// Returns the HTTP status the equivalent single-todo request would have had
+async fn apply(
+    conn: &mut PgConnection,
+    user_id: Uuid,
+    step: &Step<'_>,
+) -> Result<(u16, Option<Todo>), AppError> {
+    match step {
+        Step::Create(todo) => {
+            let recurrence = parse_recurrence(todo.recurrence.as_deref())?;
+            let created = database::create_todo(conn, user_id, todo, recurrence.as_ref()).await?;
+            Ok((201, Some(created)))
+        }
+        Step::Update(id, changes) => {
+            let recurrence = parse_recurrence(changes.recurrence_rule())?;
+            match database::update_todo(conn, user_id, *id, changes, recurrence.as_ref()).await? {
+                Some(updated) => Ok((200, Some(updated))),
+                None => Err(AppError::NotFound(format!("Todo with id {} not found", id))),
+            }
+        }
+        Step::Delete(id) => {
+            if database::delete_todo(conn, user_id, *id).await? {
+                Ok((204, None))
+            } else {
+                Err(AppError::NotFound(format!("Todo with id {} not found", id)))
+            }
+        }
+    }
+}
+
+fn too_many() -> AppError {
+    AppError::BadRequest(format!(
+        "A bulk request can change at most {} todos",
+        MAX_BULK_ITEMS
+    ))
+}
+
+pub async fn run(
+    pool: &PgPool,
+    user_id: Uuid,
+    request: BulkRequest,
+) -> Result<BulkResponse, AppError> {
+    let db_error = |e: sqlx::Error| AppError::InternalError(format!("Database error: {}", e));
+
+    let mut tx = pool.begin().await.map_err(db_error)?;
+
+    let steps: Vec<Step> = match (&request.operations, request.filter, request.action) {
+        (Some(operations), None, None) => {
+            if request.changes.is_some() {
+                return Err(AppError::BadRequest(
+                    "changes is only used with filter and action".to_string(),
+                ));
+            }
+            if operations.is_empty() {
+                return Err(AppError::BadRequest(
+                    "operations cannot be empty".to_string(),
+                ));
+            }
+            if operations.len() > MAX_BULK_ITEMS {
+                return Err(too_many());
+            }
+
+            operations
+                .iter()
+                .map(|operation| match operation {
+                    BulkOperation::Create(todo) => Step::Create(todo),
+                    BulkOperation::Update { id, changes } => Step::Update(*id, changes),
+                    BulkOperation::Delete { id } => Step::Delete(*id),
+                })
+                .collect()
+        }
+        (None, Some(filter), Some(action)) => {
+            // Resolved inside the transaction so the matched set is what gets changed
+            let query = TodoListQuery::from(filter);
+            let ids = database::list_todo_ids(&mut tx, user_id, &query, MAX_BULK_ITEMS as i64 + 1)
+                .await?;
+            if ids.len() > MAX_BULK_ITEMS {
+                return Err(too_many());
+            }
+
+            match (action, &request.changes) {
+                (BulkAction::Update, Some(changes)) => ids
+                    .into_iter()
+                    .map(|id| Step::Update(id, changes))
+                    .collect(),
+                (BulkAction::Update, None) => {
+                    return Err(AppError::BadRequest(
+                        "action \"update\" requires changes".to_string(),
+                    ));
+                }
+                (BulkAction::Delete, None) => ids.into_iter().map(Step::Delete).collect(),
+                (BulkAction::Delete, Some(_)) => {
+                    return Err(AppError::BadRequest(
+                        "action \"delete\" does not take changes".to_string(),
+                    ));
+                }
+            }
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "Provide either operations, or filter together with action".to_string(),
+            ));
+        }
+    };
+
+    let mut results = Vec::with_capacity(steps.len());
+    let mut committed = true;
+
+    for (index, step) in steps.iter().enumerate() {
+        let outcome = match request.mode {
+            BulkMode::Atomic => apply(&mut tx, user_id, step).await,
+            // Each item gets a savepoint so a failure only undoes that item
+            BulkMode::BestEffort => {
+                let mut savepoint = (&mut tx).begin().await.map_err(db_error)?;
+                let outcome = apply(&mut savepoint, user_id, step).await;
+                if outcome.is_ok() {
+                    savepoint.commit().await.map_err(db_error)?;
+                } else {
+                    savepoint.rollback().await.map_err(db_error)?;
+                }
+                outcome
+            }
+        };
+
+        let result = match outcome {
+            Ok((status, todo)) => BulkItemResult {
+                index,
+                op: step.name().to_string(),
+                id: todo.as_ref().map(|todo| todo.id).or(step.id()),
+                status,
+                todo,
+                error: None,
+            },
+            Err(e @ AppError::InternalError(_)) if request.mode == BulkMode::Atomic => {
+                return Err(e);
+            }
+            Err(e) => BulkItemResult {
+                index,
+                op: step.name().to_string(),
+                id: step.id(),
+                status: e.status_code().as_u16(),
+                todo: None,
+                error: Some(e.to_response()),
+            },
+        };
+
+        let failed = result.error.is_some();
+        results.push(result);
+
+        if failed && request.mode == BulkMode::Atomic {
+            committed = false;
+            break;
+        }
+    }
+
+    if committed {
+        tx.commit().await.map_err(db_error)?;
+    } else {
+        tx.rollback().await.map_err(db_error)?;
+    }
+
+    let failed = results.iter().filter(|r| r.error.is_some()).count();
+
+    Ok(BulkResponse {
+        mode: request.mode,
+        committed,
+        succeeded: if committed { results.len() - failed } else { 0 },
+        failed,
+        results,
+    })
+}
@@ -0,0 +1,528 @@
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::http::header::{self, CacheControl, CacheDirective, ETag, EntityTag, IfNoneMatch};
+use actix_web::middleware::Next;
+use actix_web::web::Bytes;
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, web};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Instant;
+use uuid::Uuid;
+
+use crate::auth;
+use crate::config::CacheSettings;
+use crate::database;
+use crate::errors::AppError;
+use crate::models::CacheStats;
+
+// A serialized response body and its ETag
+#[derive(Clone)]
+pub struct CachedBody {
+    body: Bytes,
+    etag: EntityTag,
+}
+
+impl CachedBody {
+    pub fn new(body: Vec<u8>) -> Self {
+        // 128 bits of the SHA-256 of the body are plenty to tell versions apart
+        let digest = Sha256::digest(&body);
+        CachedBody {
+            etag: EntityTag::new_strong(hex::encode(&digest[..16])),
+            body: Bytes::from(body),
+        }
+    }
+
+    pub fn json(value: &impl Serialize) -> Result<Self, AppError> {
+        let body = serde_json::to_vec(value)
+            .map_err(|e| AppError::InternalError(format!("Serialization error: {}", e)))?;
+        Ok(CachedBody::new(body))
+    }
+
+    // 304 when the client already has this version, the JSON body otherwise.
+    // no-cache makes clients revalidate instead of reusing a stale copy.
+    pub fn respond(&self, req: &HttpRequest) -> HttpResponse {
+        let fresh = match req.get_header::<IfNoneMatch>() {
+            Some(IfNoneMatch::Any) => true,
+            Some(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+            None => false,
+        };
+
+        let mut response = if fresh {
+            HttpResponse::NotModified()
+        } else {
+            HttpResponse::Ok()
+        };
+        response
+            .insert_header(ETag(self.etag.clone()))
+            .insert_header(CacheControl(vec![
+                CacheDirective::Private,
+                CacheDirective::NoCache,
+            ]));
+
+        if fresh {
+            response.finish()
+        } else {
+            response
+                .content_type(header::ContentType::json())
+                .body(self.body.clone())
+        }
+    }
+}
+
+struct Entry {
+    value: CachedBody,
+    stored_at: Instant,
+}
+
+// In-process cache of todo list pages, keyed by user and request URI. Writes
+// through the API drop the pages of every user who can see the change, so a
+// hit is never older than the last local change; the TTL bounds staleness
+// for changes made elsewhere.
+pub struct ListCache {
+    settings: CacheSettings,
+    entries: Mutex<HashMap<(Uuid, String), Entry>>,
+    // Bumped by every invalidation, so a page loaded before a write is not
+    // stored after it
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ListCache {
+    pub fn new(settings: CacheSettings) -> Self {
+        ListCache {
+            settings,
+            entries: Mutex::new(HashMap::new()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    // Serves the page for `req` from the cache, or builds it with `load`
+    pub async fn page<T, F>(
+        &self,
+        req: &HttpRequest,
+        user_id: Uuid,
+        load: F,
+    ) -> Result<HttpResponse, AppError>
+    where
+        T: Serialize,
+        F: Future<Output = Result<T, AppError>>,
+    {
+        if !self.settings.enabled {
+            return Ok(CachedBody::json(&load.await?)?.respond(req));
+        }
+
+        let key = (user_id, req.uri().to_string());
+        if let Some(value) = self.get(&key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value.respond(req));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let value = CachedBody::json(&load.await?)?;
+        self.insert(key, generation, value.clone());
+
+        Ok(value.respond(req))
+    }
+
+    fn get(&self, key: &(Uuid, String)) -> Option<CachedBody> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|entry| entry.stored_at.elapsed() < self.settings.ttl)
+            .map(|entry| entry.value.clone())
+    }
+
+    fn insert(&self, key: (Uuid, String), generation: u64, value: CachedBody) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        // Checked under the lock, which invalidate() also takes
+        if self.generation.load(Ordering::Acquire) != generation {
+            return;
+        }
+
+        if entries.len() >= self.settings.max_entries && !entries.contains_key(&key) {
+            let ttl = self.settings.ttl;
+            entries.retain(|_, entry| entry.stored_at.elapsed() < ttl);
+            if entries.len() >= self.settings.max_entries
+                && let Some(oldest) = entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.stored_at)
+                    .map(|(key, _)| key.clone())
+            {
+                entries.remove(&oldest);
+            }
+        }
+
+        entries.insert(
+            key,
+            Entry {
+                value,
+                stored_at: Instant::now(),
+            },
+        );
+    }
+
+    pub fn invalidate(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.clear();
+    }
+
+    // Drops the given users' pages and leaves everyone else's
+    pub fn invalidate_users<'a>(&self, users: impl IntoIterator<Item = &'a Uuid>) {
+        let users: HashSet<&Uuid> = users.into_iter().collect();
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        entries.retain(|(user_id, _), _| !users.contains(user_id));
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            enabled: self.settings.enabled,
+            entries: self.entries.lock().unwrap_or_else(|e| e.into_inner()).len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+// Whose cached pages a write can change
+#[derive(Debug, PartialEq)]
+enum Affected {
+    Nobody,
+    // Labels are private, and a new list has no other members yet
+    Caller,
+    // A todo write can touch any list the caller belongs to
+    CoMembers,
+    // The members of the list in the path
+    List(i32),
+    // Member changes and list deletion, which also affect the users who
+    // are members only before the write
+    Membership(i32),
+}
+
+// `path` is relative to the /api/v1 scope
+fn affected_by(method: &Method, path: &str) -> Affected {
+    if method.is_safe() {
+        return Affected::Nobody;
+    }
+
+    let segments: Vec<&str> = path.trim_start_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["todos", ..] => Affected::CoMembers,
+        ["labels", ..] | ["lists"] => Affected::Caller,
+        ["lists", id, rest @ ..] => match id.parse() {
+            Ok(id) if rest.first() == Some(&"members") => Affected::Membership(id),
+            Ok(id) if rest.is_empty() && method == Method::DELETE => Affected::Membership(id),
+            Ok(id) => Affected::List(id),
+            Err(_) => Affected::Nobody,
+        },
+        _ => Affected::Nobody,
+    }
+}
+
+// Drops the cached pages a successful todo, list or label write can change.
+// Runs after the handler, so the change is committed by the time the pages
+// are dropped. Failed writes change nothing and cost no lookups.
+pub async fn invalidate_on_write(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let affected = affected_by(req.method(), req.match_info().unprocessed());
+    let target = match affected {
+        Affected::Nobody => None,
+        _ => req
+            .app_data::<web::Data<ListCache>>()
+            .cloned()
+            .filter(|cache| cache.settings.enabled)
+            .zip(req.app_data::<web::Data<PgPool>>().cloned())
+            .zip(auth::user_id(req.request()).ok()),
+    };
+    let Some(((cache, pool), user_id)) = target else {
+        return next.call(req).await;
+    };
+
+    // Users removed from a list are no longer found after the write
+    let before = match affected {
+        Affected::Membership(list_id) => Some(database::list_member_ids(&pool, list_id).await),
+        _ => None,
+    };
+
+    let res = next.call(req).await;
+    if !res.as_ref().is_ok_and(|res| res.status().is_success()) {
+        return res;
+    }
+
+    let after = match affected {
+        Affected::CoMembers => Some(database::list_co_members(&pool, user_id).await),
+        Affected::List(list_id) | Affected::Membership(list_id) => {
+            Some(database::list_member_ids(&pool, list_id).await)
+        }
+        Affected::Nobody | Affected::Caller => None,
+    };
+
+    let mut users = vec![user_id];
+    for members in before.into_iter().chain(after) {
+        match members {
+            Ok(members) => users.extend(members),
+            Err(e) => {
+                log::warn!("Emptying the list cache: {}", e);
+                cache.invalidate();
+                return res;
+            }
+        }
+    }
+    cache.invalidate_users(&users);
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::StatusCode;
+    use actix_web::test::TestRequest;
+    use std::time::Duration;
+
+    fn list_cache(enabled: bool) -> ListCache {
+        ListCache::new(CacheSettings {
+            enabled,
+            ttl: Duration::from_secs(60),
+            max_entries: 10,
+        })
+    }
+
+    fn etag_of(response: &HttpResponse) -> String {
+        response
+            .headers()
+            .get(header::ETAG)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    async fn get(
+        cache: &ListCache,
+        user_id: Uuid,
+        uri: &str,
+        value: &str,
+    ) -> Result<HttpResponse, AppError> {
+        let req = TestRequest::get().uri(uri).to_http_request();
+        cache
+            .page(&req, user_id, async { Ok(value.to_string()) })
+            .await
+    }
+
+    async fn body_of(response: HttpResponse) -> Bytes {
+        actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn etag_is_the_first_128_bits_of_the_body_sha256() {
+        let cached = CachedBody::new(b"hello".to_vec());
+        let response = cached.respond(&TestRequest::default().to_http_request());
+
+        assert_eq!(etag_of(&response), "\"2cf24dba5fb0a30e26e83b2ac5b9e29e\"");
+        assert_eq!(
+            response
+                .headers()
+                .get(header::CACHE_CONTROL)
+                .unwrap()
+                .to_str()
+                .unwrap(),
+            "private, no-cache"
+        );
+    }
+
+    #[actix_web::test]
+    async fn matching_if_none_match_gets_304_without_a_body() {
+        let cached = CachedBody::json(&vec![1, 2, 3]).unwrap();
+        let etag = etag_of(&cached.respond(&TestRequest::default().to_http_request()));
+
+        for value in [
+            etag.clone(),
+            format!("W/{}", etag),
+            "\"other\", ".to_string() + &etag,
+            "*".to_string(),
+        ] {
+            let req = TestRequest::default()
+                .insert_header((header::IF_NONE_MATCH, value.as_str()))
+                .to_http_request();
+            let response = cached.respond(&req);
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", value);
+            assert_eq!(etag_of(&response), etag);
+            assert!(body_of(response).await.is_empty());
+        }
+    }
+
+    #[actix_web::test]
+    async fn other_if_none_match_gets_the_body() {
+        let cached = CachedBody::json(&vec![1, 2, 3]).unwrap();
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, "\"stale\""))
+            .to_http_request();
+
+        let response = cached.respond(&req);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body_of(response).await, "[1,2,3]");
+    }
+
+    #[actix_web::test]
+    async fn second_request_is_a_hit() {
+        let cache = list_cache(true);
+        let user = Uuid::new_v4();
+
+        get(&cache, user, "/api/v1/todos", "first").await.unwrap();
+        let response = get(&cache, user, "/api/v1/todos", "second").await.unwrap();
+
+        assert_eq!(body_of(response).await, "\"first\"");
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+    }
+
+    #[actix_web::test]
+    async fn other_users_and_uris_miss() {
+        let cache = list_cache(true);
+        let user = Uuid::new_v4();
+
+        get(&cache, user, "/api/v1/todos", "mine").await.unwrap();
+        let other_user = get(&cache, Uuid::new_v4(), "/api/v1/todos", "theirs")
+            .await
+            .unwrap();
+        let other_uri = get(&cache, user, "/api/v1/todos?page=2", "page 2")
+            .await
+            .unwrap();
+
+        assert_eq!(body_of(other_user).await, "\"theirs\"");
+        assert_eq!(body_of(other_uri).await, "\"page 2\"");
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (0, 3, 3));
+    }
+
+    #[actix_web::test]
+    async fn disabled_cache_stores_nothing() {
+        let cache = list_cache(false);
+        let user = Uuid::new_v4();
+
+        get(&cache, user, "/api/v1/todos", "first").await.unwrap();
+        let response = get(&cache, user, "/api/v1/todos", "second").await.unwrap();
+
+        assert_eq!(body_of(response).await, "\"second\"");
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[actix_web::test]
+    async fn load_errors_are_not_cached() {
+        let cache = list_cache(true);
+        let req = TestRequest::get().uri("/api/v1/todos").to_http_request();
+
+        let result = cache
+            .page(&req, Uuid::new_v4(), async {
+                Err::<String, _>(AppError::InternalError("down".to_string()))
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[actix_web::test]
+    async fn invalidate_users_drops_only_their_pages() {
+        let cache = list_cache(true);
+        let (writer, member, stranger) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for user in [writer, member, stranger] {
+            get(&cache, user, "/api/v1/todos", "old").await.unwrap();
+            get(&cache, user, "/api/v1/lists/1/todos", "old")
+                .await
+                .unwrap();
+        }
+
+        cache.invalidate_users(&[writer, member]);
+
+        assert_eq!(cache.stats().entries, 2);
+        for user in [writer, member] {
+            let response = get(&cache, user, "/api/v1/todos", "new").await.unwrap();
+            assert_eq!(body_of(response).await, "\"new\"");
+        }
+        let response = get(&cache, stranger, "/api/v1/todos", "new").await.unwrap();
+        assert_eq!(body_of(response).await, "\"old\"");
+    }
+
+    #[actix_web::test]
+    async fn invalidate_drops_every_page() {
+        let cache = list_cache(true);
+        for _ in 0..3 {
+            get(&cache, Uuid::new_v4(), "/api/v1/todos", "old")
+                .await
+                .unwrap();
+        }
+
+        cache.invalidate();
+
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[actix_web::test]
+    async fn page_loaded_before_an_invalidation_is_not_stored() {
+        let cache = list_cache(true);
+        let user = Uuid::new_v4();
+        let req = TestRequest::get().uri("/api/v1/todos").to_http_request();
+
+        let response = cache
+            .page(&req, user, async {
+                // A write lands while the page is being loaded
+                cache.invalidate_users(&[Uuid::new_v4()]);
+                Ok("old")
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(body_of(response).await, "\"old\"");
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn only_todo_list_and_label_writes_invalidate() {
+        let cases = [
+            (Method::GET, "/todos", Affected::Nobody),
+            (Method::HEAD, "/lists/1/todos", Affected::Nobody),
+            (Method::POST, "/auth/login", Affected::Nobody),
+            (Method::POST, "/auth/register", Affected::Nobody),
+            (Method::POST, "/webhooks", Affected::Nobody),
+            (Method::DELETE, "/webhooks/3", Affected::Nobody),
+            (Method::POST, "/todosx", Affected::Nobody),
+            (Method::POST, "/todos", Affected::CoMembers),
+            (Method::PATCH, "/todos/1", Affected::CoMembers),
+            (Method::POST, "/todos/import", Affected::CoMembers),
+            (Method::POST, "/labels", Affected::Caller),
+            (Method::DELETE, "/labels/4", Affected::Caller),
+            (Method::POST, "/lists", Affected::Caller),
+            (Method::PUT, "/lists/2", Affected::List(2)),
+            (Method::POST, "/lists/2/restore", Affected::List(2)),
+            (Method::PUT, "/lists/2/todos/order", Affected::List(2)),
+            (Method::DELETE, "/lists/2", Affected::Membership(2)),
+            (Method::POST, "/lists/2/members", Affected::Membership(2)),
+            (
+                Method::DELETE,
+                "/lists/2/members/x",
+                Affected::Membership(2),
+            ),
+            (Method::POST, "/lists/x/todos", Affected::Nobody),
+        ];
+
+        for (method, path, expected) in cases {
+            assert_eq!(affected_by(&method, path), expected, "{} {}", method, path);
+        }
+    }
+}
@@ -0,0 +1,768 @@
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use std::fmt;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// Precedence (lowest to highest): defaults, TOML file, environment, CLI flags.
+// clap resolves the last two for us via `env = ...` on each flag.
+
+const DEFAULT_CONFIG_FILE: &str = "config.toml";
+const MIN_JWT_SECRET_LEN: usize = 32;
+
+// Keeps credentials (e.g. the password in DATABASE_URL) out of printed settings
+#[derive(Clone, Deserialize)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[REDACTED]")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DatabaseSettings {
+    pub url: Secret,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    pub acquire_timeout: Duration,
+    // None disables closing idle connections
+    pub idle_timeout: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthSettings {
+    pub jwt_secret: Secret,
+    pub token_expiration_hours: i64,
+}
+
+#[derive(Debug, Clone)]
+pub enum NotifierKind {
+    Log,
+    Webhook { url: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct ReminderSettings {
+    pub enabled: bool,
+    // How often the scheduler looks for todos coming due
+    pub interval: Duration,
+    // How far ahead of due_at a reminder is sent
+    pub lead_time: Duration,
+    pub notifier: NotifierKind,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookSettings {
+    pub enabled: bool,
+    // How often the worker looks for deliveries to send
+    pub interval: Duration,
+    // Deliveries that fail this many times are dead-lettered
+    pub max_attempts: i32,
+    // Delay before the first retry; doubled after each further failure
+    pub retry_base: Duration,
+    // Lets webhooks target loopback, link-local and private addresses
+    pub allow_private_targets: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct RetentionSettings {
+    pub enabled: bool,
+    // How often the job looks for todos to archive or delete
+    pub interval: Duration,
+    // Completed todos are archived this long after completion
+    pub archive_after: Duration,
+    // Archived todos are deleted this long after archiving; None keeps them
+    pub delete_after: Option<Duration>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CacheSettings {
+    pub enabled: bool,
+    // Upper bound on how stale a cached page can get when the change was
+    // made outside this process (another instance, psql)
+    pub ttl: Duration,
+    pub max_entries: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Settings {
+    // host:port as configured; hostnames such as localhost:8080 are allowed
+    pub bind_address: String,
+    pub workers: Option<usize>,
+    pub log_level: String,
+    pub database: DatabaseSettings,
+    pub auth: AuthSettings,
+    pub reminders: ReminderSettings,
+    pub webhooks: WebhookSettings,
+    pub retention: RetentionSettings,
+    pub cache: CacheSettings,
+    pub repair_priorities: bool,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum OpenApiFormat {
+    Json,
+    Yaml,
+}
+
+// What the binary was asked to do. Printing the OpenAPI document needs no
+// settings, so it works without a database or JWT secret.
+#[derive(Debug)]
+pub enum Command {
+    Serve(Box<Settings>),
+    PrintOpenApi(OpenApiFormat),
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    File(String),
+    Missing(String),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::File(msg) => write!(f, "Config file error: {}", msg),
+            ConfigError::Missing(msg) => write!(f, "Missing required setting: {}", msg),
+            ConfigError::Invalid(msg) => write!(f, "Invalid setting: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Default, Parser)]
+#[command(version, about = "Todo API")]
+struct Cli {
+    /// Path to a TOML config file (defaults to ./config.toml if present)
+    #[arg(long, env = "CONFIG_FILE")]
+    config: Option<PathBuf>,
+
+    /// Address to listen on, e.g. 127.0.0.1:8080
+    #[arg(long, env = "BIND_ADDRESS")]
+    bind_address: Option<String>,
+
+    /// Number of HTTP worker threads
+    #[arg(long, env = "WORKERS")]
+    workers: Option<usize>,
+
+    /// Log filter, e.g. info or actix_web=debug
+    #[arg(long, env = "LOG_LEVEL")]
+    log_level: Option<String>,
+
+    #[arg(long, env = "DATABASE_URL", hide_env_values = true)]
+    database_url: Option<String>,
+
+    #[arg(long, env = "DATABASE_MAX_CONNECTIONS")]
+    database_max_connections: Option<u32>,
+
+    #[arg(long, env = "DATABASE_MIN_CONNECTIONS")]
+    database_min_connections: Option<u32>,
+
+    /// Seconds to wait for a free pool connection
+    #[arg(long, env = "DATABASE_ACQUIRE_TIMEOUT_SECS")]
+    database_acquire_timeout_secs: Option<u64>,
+
+    /// Seconds before an idle pool connection is closed (0 = never)
+    #[arg(long, env = "DATABASE_IDLE_TIMEOUT_SECS")]
+    database_idle_timeout_secs: Option<u64>,
+
+    /// Secret used to sign access tokens (at least 32 characters)
+    #[arg(long, env = "JWT_SECRET", hide_env_values = true)]
+    jwt_secret: Option<String>,
+
+    /// Hours before an access token expires
+    #[arg(long, env = "JWT_EXPIRATION_HOURS")]
+    jwt_expiration_hours: Option<i64>,
+
+    /// Enable the due-date reminder scheduler
+    #[arg(long, env = "REMINDERS_ENABLED")]
+    reminders_enabled: Option<bool>,
+
+    /// Seconds between reminder scans
+    #[arg(long, env = "REMINDERS_INTERVAL_SECS")]
+    reminders_interval_secs: Option<u64>,
+
+    /// Send reminders this many seconds before a todo is due
+    #[arg(long, env = "REMINDERS_LEAD_TIME_SECS")]
+    reminders_lead_time_secs: Option<u64>,
+
+    /// log or webhook
+    #[arg(long, env = "REMINDERS_NOTIFIER")]
+    reminders_notifier: Option<String>,
+
+    /// Target URL for the webhook notifier
+    #[arg(long, env = "REMINDERS_WEBHOOK_URL")]
+    reminders_webhook_url: Option<String>,
+
+    /// Run the webhook delivery worker
+    #[arg(long, env = "WEBHOOKS_ENABLED")]
+    webhooks_enabled: Option<bool>,
+
+    /// Seconds between scans for webhook deliveries to send
+    #[arg(long, env = "WEBHOOKS_INTERVAL_SECS")]
+    webhooks_interval_secs: Option<u64>,
+
+    /// Attempts before a webhook delivery is dead-lettered
+    #[arg(long, env = "WEBHOOKS_MAX_ATTEMPTS")]
+    webhooks_max_attempts: Option<i32>,
+
+    /// Seconds before the first retry of a failed delivery (doubled after each failure)
+    #[arg(long, env = "WEBHOOKS_RETRY_BASE_SECS")]
+    webhooks_retry_base_secs: Option<u64>,
+
+    /// Allow webhook URLs on this host or the local network
+    #[arg(long, env = "WEBHOOKS_ALLOW_PRIVATE_TARGETS")]
+    webhooks_allow_private_targets: Option<bool>,
+
+    /// Run the job that archives and deletes old completed todos
+    #[arg(long, env = "RETENTION_ENABLED")]
+    retention_enabled: Option<bool>,
+
+    /// Seconds between retention runs
+    #[arg(long, env = "RETENTION_INTERVAL_SECS")]
+    retention_interval_secs: Option<u64>,
+
+    /// Archive todos completed more than this many days ago
+    #[arg(long, env = "RETENTION_ARCHIVE_AFTER_DAYS")]
+    retention_archive_after_days: Option<u64>,
+
+    /// Delete todos archived more than this many days ago (unset keeps them)
+    #[arg(long, env = "RETENTION_DELETE_AFTER_DAYS")]
+    retention_delete_after_days: Option<u64>,
+
+    /// Cache todo list pages in memory
+    #[arg(long, env = "CACHE_ENABLED")]
+    cache_enabled: Option<bool>,
+
+    /// Seconds a cached page is served before it is loaded again
+    #[arg(long, env = "CACHE_TTL_SECS")]
+    cache_ttl_secs: Option<u64>,
+
+    /// Pages kept in the cache
+    #[arg(long, env = "CACHE_MAX_ENTRIES")]
+    cache_max_entries: Option<usize>,
+
+    /// Fix todos whose priority is not low/medium/high/urgent, report the changes and exit
+    #[arg(long)]
+    repair_priorities: bool,
+
+    /// Print the OpenAPI document (json by default) and exit
+    #[arg(long, value_enum, value_name = "FORMAT", num_args = 0..=1, default_missing_value = "json")]
+    print_openapi: Option<OpenApiFormat>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileSettings {
+    bind_address: Option<String>,
+    workers: Option<usize>,
+    log_level: Option<String>,
+    database: FileDatabaseSettings,
+    auth: FileAuthSettings,
+    reminders: FileReminderSettings,
+    webhooks: FileWebhookSettings,
+    retention: FileRetentionSettings,
+    cache: FileCacheSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileDatabaseSettings {
+    url: Option<Secret>,
+    max_connections: Option<u32>,
+    min_connections: Option<u32>,
+    acquire_timeout_secs: Option<u64>,
+    idle_timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileAuthSettings {
+    jwt_secret: Option<Secret>,
+    token_expiration_hours: Option<i64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileReminderSettings {
+    enabled: Option<bool>,
+    interval_secs: Option<u64>,
+    lead_time_secs: Option<u64>,
+    notifier: Option<String>,
+    webhook_url: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileWebhookSettings {
+    enabled: Option<bool>,
+    interval_secs: Option<u64>,
+    max_attempts: Option<i32>,
+    retry_base_secs: Option<u64>,
+    allow_private_targets: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileRetentionSettings {
+    enabled: Option<bool>,
+    interval_secs: Option<u64>,
+    archive_after_days: Option<u64>,
+    delete_after_days: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct FileCacheSettings {
+    enabled: Option<bool>,
+    ttl_secs: Option<u64>,
+    max_entries: Option<usize>,
+}
+
+pub fn load() -> Result<Command, ConfigError> {
+    let cli = Cli::parse();
+    if let Some(format) = cli.print_openapi {
+        return Ok(Command::PrintOpenApi(format));
+    }
+
+    let file = load_file(cli.config.as_ref())?;
+    Settings::merge(cli, file).map(|settings| Command::Serve(Box::new(settings)))
+}
+
+impl Settings {
+    fn merge(cli: Cli, file: FileSettings) -> Result<Self, ConfigError> {
+        let bind_address = cli
+            .bind_address
+            .or(file.bind_address)
+            .unwrap_or_else(|| "127.0.0.1:8080".to_string());
+        // Resolved once here so a bad address fails at startup with a clear
+        // message; the server resolves it again when binding
+        if bind_address.to_socket_addrs().is_err() {
+            return Err(ConfigError::Invalid(format!(
+                "bind_address '{}' is not a resolvable host:port",
+                bind_address
+            )));
+        }
+
+        let workers = cli.workers.or(file.workers);
+        if workers == Some(0) {
+            return Err(ConfigError::Invalid(
+                "workers must be at least 1".to_string(),
+            ));
+        }
+
+        let log_level = cli
+            .log_level
+            .or(file.log_level)
+            .unwrap_or_else(|| "info".to_string());
+
+        let url = cli
+            .database_url
+            .map(Secret)
+            .or(file.database.url)
+            .ok_or_else(|| ConfigError::Missing("database.url (DATABASE_URL)".to_string()))?;
+        let max_connections = cli
+            .database_max_connections
+            .or(file.database.max_connections)
+            .unwrap_or(10);
+        let min_connections = cli
+            .database_min_connections
+            .or(file.database.min_connections)
+            .unwrap_or(0);
+
+        if max_connections == 0 {
+            return Err(ConfigError::Invalid(
+                "database.max_connections must be at least 1".to_string(),
+            ));
+        }
+        if min_connections > max_connections {
+            return Err(ConfigError::Invalid(
+                "database.min_connections cannot exceed database.max_connections".to_string(),
+            ));
+        }
+        let acquire_timeout_secs = cli
+            .database_acquire_timeout_secs
+            .or(file.database.acquire_timeout_secs)
+            .unwrap_or(5);
+        if acquire_timeout_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "database.acquire_timeout_secs must be at least 1".to_string(),
+            ));
+        }
+        let idle_timeout_secs = cli
+            .database_idle_timeout_secs
+            .or(file.database.idle_timeout_secs)
+            .unwrap_or(600);
+
+        let jwt_secret = cli
+            .jwt_secret
+            .map(Secret)
+            .or(file.auth.jwt_secret)
+            .ok_or_else(|| ConfigError::Missing("auth.jwt_secret (JWT_SECRET)".to_string()))?;
+        if jwt_secret.expose().len() < MIN_JWT_SECRET_LEN {
+            return Err(ConfigError::Invalid(format!(
+                "auth.jwt_secret must be at least {} characters",
+                MIN_JWT_SECRET_LEN
+            )));
+        }
+        let token_expiration_hours = cli
+            .jwt_expiration_hours
+            .or(file.auth.token_expiration_hours)
+            .unwrap_or(24);
+        if token_expiration_hours <= 0 {
+            return Err(ConfigError::Invalid(
+                "auth.token_expiration_hours must be positive".to_string(),
+            ));
+        }
+
+        let reminders_interval_secs = cli
+            .reminders_interval_secs
+            .or(file.reminders.interval_secs)
+            .unwrap_or(60);
+        if reminders_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "reminders.interval_secs must be at least 1".to_string(),
+            ));
+        }
+        let reminders_lead_time_secs = cli
+            .reminders_lead_time_secs
+            .or(file.reminders.lead_time_secs)
+            .unwrap_or(3600);
+
+        let webhook_url = cli.reminders_webhook_url.or(file.reminders.webhook_url);
+        let notifier = match cli
+            .reminders_notifier
+            .or(file.reminders.notifier)
+            .as_deref()
+            .unwrap_or("log")
+        {
+            "log" => NotifierKind::Log,
+            "webhook" => NotifierKind::Webhook {
+                url: webhook_url.ok_or_else(|| {
+                    ConfigError::Missing(
+                        "reminders.webhook_url (REMINDERS_WEBHOOK_URL) for the webhook notifier"
+                            .to_string(),
+                    )
+                })?,
+            },
+            other => {
+                return Err(ConfigError::Invalid(format!(
+                    "reminders.notifier '{}', expected 'log' or 'webhook'",
+                    other
+                )));
+            }
+        };
+
+        let reminders = ReminderSettings {
+            enabled: cli
+                .reminders_enabled
+                .or(file.reminders.enabled)
+                .unwrap_or(true),
+            interval: Duration::from_secs(reminders_interval_secs),
+            lead_time: Duration::from_secs(reminders_lead_time_secs),
+            notifier,
+        };
+
+        let webhooks_interval_secs = cli
+            .webhooks_interval_secs
+            .or(file.webhooks.interval_secs)
+            .unwrap_or(5);
+        if webhooks_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "webhooks.interval_secs must be at least 1".to_string(),
+            ));
+        }
+        let webhooks_max_attempts = cli
+            .webhooks_max_attempts
+            .or(file.webhooks.max_attempts)
+            .unwrap_or(8);
+        if webhooks_max_attempts < 1 {
+            return Err(ConfigError::Invalid(
+                "webhooks.max_attempts must be at least 1".to_string(),
+            ));
+        }
+        let webhooks_retry_base_secs = cli
+            .webhooks_retry_base_secs
+            .or(file.webhooks.retry_base_secs)
+            .unwrap_or(30);
+        if webhooks_retry_base_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "webhooks.retry_base_secs must be at least 1".to_string(),
+            ));
+        }
+
+        let webhooks = WebhookSettings {
+            enabled: cli
+                .webhooks_enabled
+                .or(file.webhooks.enabled)
+                .unwrap_or(true),
+            interval: Duration::from_secs(webhooks_interval_secs),
+            max_attempts: webhooks_max_attempts,
+            retry_base: Duration::from_secs(webhooks_retry_base_secs),
+            allow_private_targets: cli
+                .webhooks_allow_private_targets
+                .or(file.webhooks.allow_private_targets)
+                .unwrap_or(false),
+        };
+
+        let retention_interval_secs = cli
+            .retention_interval_secs
+            .or(file.retention.interval_secs)
+            .unwrap_or(3600);
+        if retention_interval_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "retention.interval_secs must be at least 1".to_string(),
+            ));
+        }
+        let retention_archive_after_days = cli
+            .retention_archive_after_days
+            .or(file.retention.archive_after_days)
+            .unwrap_or(30);
+        let retention_delete_after_days = cli
+            .retention_delete_after_days
+            .or(file.retention.delete_after_days);
+        if retention_delete_after_days == Some(0) {
+            return Err(ConfigError::Invalid(
+                "retention.delete_after_days must be at least 1".to_string(),
+            ));
+        }
+
+        let retention = RetentionSettings {
+            enabled: cli
+                .retention_enabled
+                .or(file.retention.enabled)
+                .unwrap_or(false),
+            interval: Duration::from_secs(retention_interval_secs),
+            archive_after: days(retention_archive_after_days),
+            delete_after: retention_delete_after_days.map(days),
+        };
+
+        let cache_ttl_secs = cli.cache_ttl_secs.or(file.cache.ttl_secs).unwrap_or(5);
+        if cache_ttl_secs == 0 {
+            return Err(ConfigError::Invalid(
+                "cache.ttl_secs must be at least 1".to_string(),
+            ));
+        }
+        let cache_max_entries = cli
+            .cache_max_entries
+            .or(file.cache.max_entries)
+            .unwrap_or(1000);
+        if cache_max_entries == 0 {
+            return Err(ConfigError::Invalid(
+                "cache.max_entries must be at least 1".to_string(),
+            ));
+        }
+
+        let cache = CacheSettings {
+            enabled: cli.cache_enabled.or(file.cache.enabled).unwrap_or(false),
+            ttl: Duration::from_secs(cache_ttl_secs),
+            max_entries: cache_max_entries,
+        };
+
+        Ok(Settings {
+            bind_address,
+            workers,
+            log_level,
+            auth: AuthSettings {
+                jwt_secret,
+                token_expiration_hours,
+            },
+            reminders,
+            webhooks,
+            retention,
+            cache,
+            database: DatabaseSettings {
+                url,
+                max_connections,
+                min_connections,
+                acquire_timeout: Duration::from_secs(acquire_timeout_secs),
+                idle_timeout: (idle_timeout_secs > 0)
+                    .then(|| Duration::from_secs(idle_timeout_secs)),
+            },
+            repair_priorities: cli.repair_priorities,
+        })
+    }
+}
+
+fn days(days: u64) -> Duration {
+    Duration::from_secs(days.saturating_mul(24 * 60 * 60))
+}
+
+// A file passed with --config must exist; ./config.toml is optional
+fn load_file(path: Option<&PathBuf>) -> Result<FileSettings, ConfigError> {
+    let (path, required) = match path {
+        Some(path) => (path.clone(), true),
+        None => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+    };
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(FileSettings::default());
+        }
+        Err(e) => return Err(ConfigError::File(format!("{}: {}", path.display(), e))),
+    };
+
+    toml::from_str(&contents).map_err(|e| ConfigError::File(format!("{}: {}", path.display(), e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    const SECRET: &str = "0123456789abcdef0123456789abcdef";
+
+    fn file(toml: &str) -> FileSettings {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn minimal_file() -> FileSettings {
+        file(&format!(
+            "[database]\nurl = \"postgres://localhost/todos\"\n[auth]\njwt_secret = \"{}\"",
+            SECRET
+        ))
+    }
+
+    fn invalid(cli: Cli, file: FileSettings) -> String {
+        match Settings::merge(cli, file) {
+            Err(ConfigError::Invalid(msg)) => msg,
+            other => panic!("expected an invalid setting, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults_apply_when_nothing_is_set() {
+        let settings = Settings::merge(Cli::default(), minimal_file()).unwrap();
+        assert_eq!(settings.bind_address, "127.0.0.1:8080");
+        assert_eq!(settings.workers, None);
+        assert_eq!(settings.log_level, "info");
+        assert_eq!(settings.auth.token_expiration_hours, 24);
+        assert_eq!(settings.database.max_connections, 10);
+        assert_eq!(settings.webhooks.max_attempts, 8);
+        assert!(!settings.webhooks.allow_private_targets);
+        assert!(!settings.retention.enabled);
+        assert!(!settings.cache.enabled);
+    }
+
+    #[test]
+    fn cli_overrides_file() {
+        let mut file = minimal_file();
+        file.workers = Some(2);
+        file.log_level = Some("warn".to_string());
+        file.cache.ttl_secs = Some(30);
+        let cli = Cli {
+            workers: Some(4),
+            cache_ttl_secs: Some(10),
+            ..Cli::default()
+        };
+
+        let settings = Settings::merge(cli, file).unwrap();
+        assert_eq!(settings.workers, Some(4));
+        assert_eq!(settings.log_level, "warn");
+        assert_eq!(settings.cache.ttl, Duration::from_secs(10));
+    }
+
+    #[test]
+    fn flags_read_their_environment_variables() {
+        // clap fills a field from the flag, or from the variable when the flag
+        // is absent, so checking the names covers env < CLI without touching
+        // the process environment
+        let command = Cli::command();
+        for (id, env) in [
+            ("bind_address", "BIND_ADDRESS"),
+            ("database_url", "DATABASE_URL"),
+            ("jwt_secret", "JWT_SECRET"),
+            ("jwt_expiration_hours", "JWT_EXPIRATION_HOURS"),
+        ] {
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .unwrap();
+            assert_eq!(arg.get_env(), Some(std::ffi::OsStr::new(env)), "{}", id);
+        }
+    }
+
+    #[test]
+    fn token_expiration_from_cli_or_env_overrides_file() {
+        let mut file = minimal_file();
+        file.auth.token_expiration_hours = Some(48);
+        let cli = Cli {
+            jwt_expiration_hours: Some(6),
+            ..Cli::default()
+        };
+
+        let settings = Settings::merge(cli, file).unwrap();
+        assert_eq!(settings.auth.token_expiration_hours, 6);
+    }
+
+    #[test]
+    fn hostnames_are_accepted_as_bind_address() {
+        let cli = Cli {
+            bind_address: Some("localhost:8080".to_string()),
+            ..Cli::default()
+        };
+        let settings = Settings::merge(cli, minimal_file()).unwrap();
+        assert_eq!(settings.bind_address, "localhost:8080");
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        let cli = Cli {
+            bind_address: Some("localhost".to_string()),
+            ..Cli::default()
+        };
+        assert!(invalid(cli, minimal_file()).contains("bind_address"));
+
+        let cli = Cli {
+            workers: Some(0),
+            ..Cli::default()
+        };
+        assert!(invalid(cli, minimal_file()).contains("workers"));
+
+        let cli = Cli {
+            jwt_secret: Some("short".to_string()),
+            ..Cli::default()
+        };
+        assert!(invalid(cli, minimal_file()).contains("jwt_secret"));
+
+        let mut file = minimal_file();
+        file.database.min_connections = Some(20);
+        assert!(invalid(Cli::default(), file).contains("min_connections"));
+
+        let mut file = minimal_file();
+        file.reminders.notifier = Some("email".to_string());
+        assert!(invalid(Cli::default(), file).contains("reminders.notifier"));
+
+        let mut file = minimal_file();
+        file.retention.delete_after_days = Some(0);
+        assert!(invalid(Cli::default(), file).contains("delete_after_days"));
+    }
+
+    #[test]
+    fn required_settings_must_be_present() {
+        let result = Settings::merge(Cli::default(), FileSettings::default());
+        assert!(matches!(result, Err(ConfigError::Missing(_))));
+
+        let mut file = minimal_file();
+        file.reminders.notifier = Some("webhook".to_string());
+        let result = Settings::merge(Cli::default(), file);
+        assert!(matches!(result, Err(ConfigError::Missing(msg)) if msg.contains("webhook_url")));
+    }
+
+    #[test]
+    fn unknown_file_keys_are_rejected() {
+        assert!(toml::from_str::<FileSettings>("bind = \"0.0.0.0:80\"").is_err());
+    }
+}
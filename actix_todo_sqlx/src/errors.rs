@@ -1,45 +1,196 @@
-use actix_web::{HttpResponse, ResponseError};
+use actix_web::dev::ServiceResponse;
+use actix_web::error::{InternalError, JsonPayloadError, PathError, QueryPayloadError};
+use actix_web::http::StatusCode;
+use actix_web::http::header;
+use actix_web::middleware::ErrorHandlerResponse;
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
 use serde::Serialize;
 use std::fmt;
 use utoipa::ToSchema;
 
+pub const PROBLEM_JSON: &str = "application/problem+json";
+
+// RFC 7807 problem details. `type` is always about:blank, so `title` is the
+// reason phrase of `status` and `detail` says what went wrong.
 #[derive(Debug, Serialize, ToSchema)]
 pub struct ErrorResponse {
-    pub error: String,
+    #[serde(rename = "type")]
+    #[schema(example = "about:blank")]
+    pub problem_type: String,
+    #[schema(example = "Not Found")]
+    pub title: String,
+    #[schema(example = 404)]
+    pub status: u16,
+    #[schema(example = "Todo 42 not found")]
+    pub detail: String,
+    /// One entry per invalid field (422 responses only)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    pub field: String,
     pub message: String,
 }
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl ErrorResponse {
+    pub fn new(status: StatusCode, detail: impl Into<String>) -> Self {
+        ErrorResponse {
+            problem_type: "about:blank".to_string(),
+            title: status.canonical_reason().unwrap_or("Error").to_string(),
+            status: status.as_u16(),
+            detail: detail.into(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn into_http(self) -> HttpResponse {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        HttpResponse::build(status)
+            .content_type(PROBLEM_JSON)
+            .json(self)
+    }
+}
+
 #[derive(Debug)]
 pub enum AppError {
     NotFound(String),
+    BadRequest(String),
+    Validation(Vec<FieldError>),
+    Unauthorized(String),
+    Forbidden(String),
+    Conflict(String),
     InternalError(String),
 }
 
+impl AppError {
+    // Body used both for error responses and for per-item bulk results
+    pub fn to_response(&self) -> ErrorResponse {
+        let status = self.status_code();
+        match self {
+            AppError::Validation(errors) => ErrorResponse {
+                errors: errors.clone(),
+                ..ErrorResponse::new(status, validation_detail(errors))
+            },
+            AppError::NotFound(msg)
+            | AppError::BadRequest(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::Forbidden(msg)
+            | AppError::Conflict(msg)
+            | AppError::InternalError(msg) => ErrorResponse::new(status, msg.clone()),
+        }
+    }
+}
+
+fn validation_detail(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|error| format!("{} {}", error.field, error.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             AppError::NotFound(msg) => write!(f, "Not Found: {}", msg),
+            AppError::BadRequest(msg) => write!(f, "Bad Request: {}", msg),
+            AppError::Validation(errors) => {
+                write!(f, "Validation Failed: {}", validation_detail(errors))
+            }
+            AppError::Unauthorized(msg) => write!(f, "Unauthorized: {}", msg),
+            AppError::Forbidden(msg) => write!(f, "Forbidden: {}", msg),
+            AppError::Conflict(msg) => write!(f, "Conflict: {}", msg),
             AppError::InternalError(msg) => write!(f, "Internal Error: {}", msg),
         }
     }
 }
 
 impl ResponseError for AppError {
-    fn error_response(&self) -> HttpResponse {
+    fn status_code(&self) -> StatusCode {
         match self {
-            AppError::NotFound(msg) => {
-                let response = ErrorResponse {
-                    error: "not_found".to_string(),
-                    message: msg.clone(),
-                };
-                HttpResponse::NotFound().json(response)
-            }
-            AppError::InternalError(msg) => {
-                let response = ErrorResponse {
-                    error: "internal_error".to_string(),
-                    message: msg.clone(),
-                };
-                HttpResponse::InternalServerError().json(response)
-            }
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AppError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AppError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AppError::Conflict(_) => StatusCode::CONFLICT,
+            AppError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    fn error_response(&self) -> HttpResponse {
+        self.to_response().into_http()
+    }
+}
+
+// Error handlers for the JsonConfig, PathConfig and QueryConfig extractors,
+// which otherwise answer with plain text. The status actix picked is kept.
+fn extractor_error<E>(err: E, status: StatusCode) -> actix_web::Error
+where
+    E: fmt::Debug + fmt::Display + 'static,
+{
+    let response = ErrorResponse::new(status, err.to_string()).into_http();
+    InternalError::from_response(err, response).into()
+}
+
+pub fn json_error_handler(err: JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let status = match err {
+        JsonPayloadError::ContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+        _ => err.status_code(),
+    };
+    extractor_error(err, status)
+}
+
+pub fn path_error_handler(err: PathError, _req: &HttpRequest) -> actix_web::Error {
+    let status = err.status_code();
+    extractor_error(err, status)
+}
+
+pub fn query_error_handler(err: QueryPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    let status = err.status_code();
+    extractor_error(err, status)
+}
+
+// Fallback for the ErrorHandlers middleware: turns the empty or plain-text
+// error responses actix produces itself (unknown routes, oversized bodies)
+// into problem details. JSON bodies, such as problem details or the results
+// of a failed bulk request, are left alone.
+pub fn problem_fallback<B>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/") && value.contains("json"));
+    if is_json {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+
+    let (req, res) = res.into_parts();
+    let status = res.status();
+    let detail = match res.error() {
+        Some(e) => e.to_string(),
+        None if status == StatusCode::NOT_FOUND => format!("No route for {}", req.path()),
+        None if status == StatusCode::METHOD_NOT_ALLOWED => {
+            format!("{} is not allowed on {}", req.method(), req.path())
+        }
+        None => status.canonical_reason().unwrap_or("Error").to_string(),
+    };
+
+    let res = ErrorResponse::new(status, detail).into_http();
+    Ok(ErrorHandlerResponse::Response(
+        ServiceResponse::new(req, res)
+            .map_into_boxed_body()
+            .map_into_right_body(),
+    ))
 }
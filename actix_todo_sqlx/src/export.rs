@@ -0,0 +1,297 @@
+use actix_web::web::Bytes;
+use chrono::{DateTime, Utc};
+use futures_util::future::ready;
+use futures_util::{Stream, StreamExt, stream};
+use sqlx::PgPool;
+use std::borrow::Cow;
+use uuid::Uuid;
+
+use crate::database;
+use crate::errors::AppError;
+use crate::models::{ExportFormat, ExportQuery, Priority, Todo, TodoListQuery};
+
+// Columns of the CSV export. The import reads the same names, so an export
+// can be imported again (id, timestamps and the other read-only columns are
+// ignored there).
+const CSV_COLUMNS: [&str; 13] = [
+    "id",
+    "title",
+    "description",
+    "completed",
+    "priority",
+    "due_at",
+    "completed_at",
+    "recurrence",
+    "parent_id",
+    "list_id",
+    "position",
+    "created_at",
+    "updated_at",
+];
+
+pub fn content_type(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Csv => "text/csv; charset=utf-8",
+        ExportFormat::Ics => "text/calendar; charset=utf-8",
+        ExportFormat::Json => "application/json",
+    }
+}
+
+pub fn file_name(format: ExportFormat) -> &'static str {
+    match format {
+        ExportFormat::Csv => "todos.csv",
+        ExportFormat::Ics => "todos.ics",
+        ExportFormat::Json => "todos.json",
+    }
+}
+
+// Spreadsheets evaluate cells starting with one of these as formulas
+const FORMULA_PREFIXES: [char; 6] = ['=', '+', '-', '@', '\t', '\r'];
+
+// Leading apostrophes are looked past, so a title that already starts with
+// one is escaped again and unescape_formula gives it back unchanged
+fn is_formula(value: &str) -> bool {
+    value.trim_start_matches('\'').starts_with(FORMULA_PREFIXES)
+}
+
+// Prefixes user text that a spreadsheet would run as a formula with an
+// apostrophe, which makes it read the cell as plain text
+pub fn escape_formula(value: &str) -> Cow<'_, str> {
+    if is_formula(value) {
+        Cow::Owned(format!("'{}", value))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+// Reverses escape_formula for cells read back by the import
+pub fn unescape_formula(value: &str) -> &str {
+    match value.strip_prefix('\'') {
+        Some(rest) if is_formula(rest) => rest,
+        _ => value,
+    }
+}
+
+// Quotes a field only when it contains a separator, quote or line break (RFC 4180)
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\r', '\n']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+fn timestamp(value: Option<DateTime<Utc>>) -> String {
+    optional(value.map(|value| value.to_rfc3339()))
+}
+
+fn csv_row(todo: &Todo) -> String {
+    let fields = [
+        todo.id.to_string(),
+        escape_formula(&todo.title).into_owned(),
+        optional(todo.description.as_deref().map(escape_formula)),
+        todo.completed.to_string(),
+        todo.priority.as_str().to_string(),
+        timestamp(todo.due_at),
+        timestamp(todo.completed_at),
+        optional(todo.recurrence.as_ref()),
+        optional(todo.parent_id),
+        optional(todo.list_id),
+        optional(todo.position),
+        timestamp(todo.created_at),
+        timestamp(todo.updated_at),
+    ];
+
+    let mut row = fields
+        .iter()
+        .map(|field| csv_field(field))
+        .collect::<Vec<_>>()
+        .join(",");
+    row.push_str("\r\n");
+    row
+}
+
+fn ics_timestamp(value: DateTime<Utc>) -> String {
+    value.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// TEXT values escape backslashes, semicolons, commas and line breaks (RFC 5545 3.3.11)
+fn ics_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\r', '\n'], "\\n")
+}
+
+// Content lines longer than 75 octets are folded onto continuation lines
+// starting with a space, without splitting a UTF-8 character
+fn ics_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+// 1 is the highest priority in iCalendar and 9 the lowest
+fn ics_priority(priority: &Priority) -> u8 {
+    match priority {
+        Priority::Urgent => 1,
+        Priority::High => 3,
+        Priority::Medium => 5,
+        Priority::Low => 9,
+    }
+}
+
+// Only todos with a due date are exported. Recurring todos are exported as
+// their current occurrence without an RRULE: the server creates each next
+// occurrence as a todo of its own once the current one is completed.
+fn ics_todo(todo: &Todo, now: DateTime<Utc>) -> Option<String> {
+    let due_at = todo.due_at?;
+
+    let mut lines = vec![
+        "BEGIN:VTODO".to_string(),
+        format!("UID:todo-{}@actix_todo_sqlx", todo.id),
+        format!("DTSTAMP:{}", ics_timestamp(now)),
+        format!("SUMMARY:{}", ics_text(&todo.title)),
+    ];
+    if let Some(description) = &todo.description {
+        lines.push(format!("DESCRIPTION:{}", ics_text(description)));
+    }
+    lines.push(format!("DUE:{}", ics_timestamp(due_at)));
+    lines.push(format!("PRIORITY:{}", ics_priority(&todo.priority)));
+    if todo.completed {
+        lines.push("STATUS:COMPLETED".to_string());
+        if let Some(completed_at) = todo.completed_at {
+            lines.push(format!("COMPLETED:{}", ics_timestamp(completed_at)));
+        }
+    } else {
+        lines.push("STATUS:NEEDS-ACTION".to_string());
+    }
+    if let Some(created_at) = todo.created_at {
+        lines.push(format!("CREATED:{}", ics_timestamp(created_at)));
+    }
+    if let Some(updated_at) = todo.updated_at {
+        lines.push(format!("LAST-MODIFIED:{}", ics_timestamp(updated_at)));
+    }
+    lines.push("END:VTODO".to_string());
+
+    let mut out = String::new();
+    for line in &lines {
+        ics_line(&mut out, line);
+    }
+    Some(out)
+}
+
+fn header(format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => format!("{}\r\n", CSV_COLUMNS.join(",")),
+        ExportFormat::Ics => {
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//actix_todo_sqlx//todos//EN\r\nCALSCALE:GREGORIAN\r\n"
+                .to_string()
+        }
+        ExportFormat::Json => "[".to_string(),
+    }
+}
+
+fn footer(format: ExportFormat) -> String {
+    match format {
+        ExportFormat::Csv => String::new(),
+        ExportFormat::Ics => "END:VCALENDAR\r\n".to_string(),
+        ExportFormat::Json => "]".to_string(),
+    }
+}
+
+fn entry(format: ExportFormat, index: usize, todo: &Todo, now: DateTime<Utc>) -> Option<String> {
+    match format {
+        ExportFormat::Csv => Some(csv_row(todo)),
+        ExportFormat::Ics => ics_todo(todo, now),
+        ExportFormat::Json => {
+            let separator = if index == 0 { "" } else { "," };
+            serde_json::to_string(todo)
+                .ok()
+                .map(|json| format!("{}{}", separator, json))
+        }
+    }
+}
+
+// The response body: one chunk per todo between the format's header and
+// footer. A database error part-way ends the stream with an error, which
+// aborts the response instead of leaving a file that looks complete.
+pub fn stream(
+    pool: PgPool,
+    user_id: Uuid,
+    query: &ExportQuery,
+) -> impl Stream<Item = Result<Bytes, actix_web::Error>> + use<> {
+    let format = query.format;
+    let now = Utc::now();
+
+    let filters = TodoListQuery {
+        completed: query.completed,
+        priority: query.priority.clone(),
+        list_id: query.list_id,
+        archived: query.archived,
+        ..Default::default()
+    };
+
+    let rows = stream::unfold(
+        database::stream_todos(pool, user_id, filters),
+        |mut rows| async move { rows.recv().await.map(|row| (row, rows)) },
+    );
+
+    let entries = rows.enumerate().filter_map(move |(index, row)| {
+        ready(match row {
+            Ok(todo) => entry(format, index, &todo, now).map(Ok),
+            Err(e) => Some(Err(e)),
+        })
+    });
+
+    stream::once(ready(Ok(header(format))))
+        .chain(entries)
+        .chain(stream::once(ready(Ok(footer(format)))))
+        .map(|chunk: Result<String, AppError>| {
+            chunk.map(Bytes::from).map_err(|e| {
+                log::error!("Export failed: {}", e);
+                actix_web::Error::from(e)
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formula_cells_are_prefixed_with_an_apostrophe() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx", "'=1+1"] {
+            assert_eq!(escape_formula(value), format!("'{}", value));
+        }
+        for value in ["", "Buy milk", "a=b", "'quoted'", "1-2"] {
+            assert_eq!(escape_formula(value), value);
+        }
+    }
+
+    #[test]
+    fn escaped_formulas_read_back_unchanged() {
+        for value in ["=HYPERLINK(\"x\")", "-1", "'=1", "''@x", "'plain", "plain"] {
+            assert_eq!(unescape_formula(&escape_formula(value)), value);
+        }
+    }
+
+    #[test]
+    fn escaped_cells_are_still_quoted() {
+        assert_eq!(csv_field(&escape_formula("=1,2")), "\"'=1,2\"");
+        assert_eq!(csv_field(&escape_formula("-\"x\"")), "\"'-\"\"x\"\"\"");
+    }
+}
@@ -1,148 +1,1804 @@
+use crate::auth;
+use crate::bulk;
+use crate::cache::{CachedBody, ListCache};
+use crate::config::{AuthSettings, WebhookSettings};
 use crate::database;
 use crate::errors::AppError;
-use crate::models::{CreateTodo, Priority, UpdateTodo};
-use actix_web::{HttpResponse, Result, web};
+use crate::export;
+use crate::import;
+use crate::models::{
+    ActivityQuery, AddDependency, AddListMember, AddTodoLabel, AuthRequest, AuthResponse,
+    BulkRequest, CacheStats, CreateLabel, CreateList, CreateTodo, CreateWebhook, DeleteListQuery,
+    DeliveriesQuery, DeliveryPage, EventPage, ExportQuery, HealthResponse, HistoryQuery,
+    ImportQuery, ListsQuery, MoveTodo, PoolStats, ReorderTodos, SearchPage, SearchQuery, SortField,
+    SortOrder, StatsQuery, TodoEvent, TodoListQuery, TodoPage, TodoQuery, TodoTree, UpcomingQuery,
+    UpdateLabel, UpdateList, UpdateTodo, UpdateWebhook,
+};
+use crate::pagination::{self, Cursor};
+use crate::recurrence::Recurrence;
+use crate::search;
+use crate::webhooks;
+use actix_web::{HttpRequest, HttpResponse, Result, http::header, web};
+use bcrypt::{DEFAULT_COST, hash, verify};
 use sqlx::PgPool;
+use std::time::Duration;
+use uuid::Uuid;
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/auth/register",
+    request_body = AuthRequest,
+    responses(
+        (status = 201, description = "User created; returns an access token", body = AuthResponse),
+        (status = 400, description = "Invalid email or password", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "User already exists", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(()),
+    tag = "auth"
+)]
+pub async fn register_handler(
+    pool: web::Data<PgPool>,
+    auth_settings: web::Data<AuthSettings>,
+    credentials: web::Json<AuthRequest>,
+) -> Result<HttpResponse, AppError> {
+    let email = credentials.email.trim().to_lowercase();
+    if !email.contains('@') {
+        return Err(AppError::BadRequest("Invalid email address".to_string()));
+    }
+    if credentials.password.len() < MIN_PASSWORD_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Password must be at least {} characters",
+            MIN_PASSWORD_LEN
+        )));
+    }
+
+    let password_hash = hash(&credentials.password, DEFAULT_COST)
+        .map_err(|e| AppError::InternalError(format!("Password hashing failed: {}", e)))?;
+
+    let user = database::create_user(pool.get_ref(), &email, &password_hash).await?;
+
+    let response = AuthResponse {
+        token: auth::create_token(&auth_settings, user.id)?,
+        user_id: user.id,
+    };
+
+    Ok(HttpResponse::Created().json(response))
+}
 
 #[utoipa::path(
     post,
-    path = "/todos",
+    path = "/api/v1/auth/login",
+    request_body = AuthRequest,
+    responses(
+        (status = 200, description = "Access token", body = AuthResponse),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    security(()),
+    tag = "auth"
+)]
+pub async fn login_handler(
+    pool: web::Data<PgPool>,
+    auth_settings: web::Data<AuthSettings>,
+    credentials: web::Json<AuthRequest>,
+) -> Result<HttpResponse, AppError> {
+    let invalid = || AppError::Unauthorized("Invalid credentials".to_string());
+
+    let email = credentials.email.trim().to_lowercase();
+    let user = database::get_user_by_email(pool.get_ref(), &email)
+        .await?
+        .ok_or_else(invalid)?;
+
+    let is_valid = verify(&credentials.password, &user.password_hash)
+        .map_err(|e| AppError::InternalError(format!("Password verification failed: {}", e)))?;
+    if !is_valid {
+        return Err(invalid());
+    }
+
+    let response = AuthResponse {
+        token: auth::create_token(&auth_settings, user.id)?,
+        user_id: user.id,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos",
     request_body = CreateTodo,
     responses(
         (status = 201, description = "Todo created successfully", body = Todo),
-        (status = 400, description = "Bad request", body = ErrorResponse),
-        (status = 404, description = "Todo not found", body = ErrorResponse),
-        (status = 500, description = "Internal server error", body = ErrorResponse)
+        (status = 400, description = "Invalid recurrence rule or parent", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access to the parent or list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Parent or list not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Empty or too long title", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
     ),
     tag = "todos"
 )]
-
 pub async fn create_todo_handler(
     pool: web::Data<PgPool>,
     todo: web::Json<CreateTodo>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
-    // // Convert Option<Priority> to &Priority with default
-    let priority = todo.priority.as_ref().unwrap_or(&Priority::Medium);
+    let user_id = auth::user_id(&req)?;
+    let recurrence = todo
+        .recurrence
+        .as_deref()
+        .map(str::parse::<Recurrence>)
+        .transpose()?;
 
-    let created_todo = database::create_todo(
-        pool.get_ref(),
-        &todo.title,
-        todo.description.as_deref(),
-        priority,
-    )
-    .await?;
+    let created_todo =
+        database::create_todo(pool.get_ref(), user_id, &todo, recurrence.as_ref()).await?;
 
     Ok(HttpResponse::Created().json(created_todo))
 }
 
 #[utoipa::path(
     get,
-    path = "/todos/{id}",
+    path = "/api/v1/todos/{id}",
     responses(
-        (status = 200, description = "Todo found successfully", body = Todo),
-        (status = 404, description = "Todo not found", body = ErrorResponse),
-        (status = 500, description = "Internal server error", body = ErrorResponse),
+        (status = 200, description = "Todo found successfully; with subtree=true it also carries `subtasks` and `completion_percent`", body = TodoTree),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json"),
     ),
     params(
-        ("id" = i32, Path, description = "Todo ID")
+        ("id" = i32, Path, description = "Todo ID"),
+        TodoQuery
     ),
     tag = "todos"
 )]
 pub async fn get_todo_handler(
     pool: web::Data<PgPool>,
     path: web::Path<i32>,
+    query: web::Query<TodoQuery>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
     let id = path.into_inner();
+    let not_found = || AppError::NotFound(format!("Todo with id {} not found", id));
 
-    match database::get_todo(pool.get_ref(), id).await? {
-        Some(todo) => Ok(HttpResponse::Ok().json(todo)),
-        None => Err(AppError::NotFound(format!("Todo with id {} not found", id))),
+    if query.subtree.unwrap_or(false) {
+        // The root comes first, followed by its descendants
+        let mut todos = database::get_todo_subtree(pool.get_ref(), user_id, id)
+            .await?
+            .into_iter();
+        let root = todos.next().ok_or_else(not_found)?;
+        return Ok(CachedBody::json(&TodoTree::build(root, todos.collect()))?.respond(&req));
+    }
+
+    match database::get_todo(pool.get_ref(), user_id, id).await? {
+        Some(todo) => Ok(CachedBody::json(&todo)?.respond(&req)),
+        None => Err(not_found()),
     }
 }
 
 #[utoipa::path(
     get,
-    path = "/todos",
+    path = "/api/v1/todos/{id}/dependencies",
     responses(
-        (status = 200, description = "List of todos retrieved successfully", body = [Todo]),
-        (status = 500, description = "Internal server error", body = ErrorResponse)
+        (status = 200, description = "Todos that must be completed first", body = [Todo]),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
     ),
     tag = "todos"
 )]
-pub async fn list_todos_handler(pool: web::Data<PgPool>) -> Result<HttpResponse, AppError> {
-    let todos = database::list_todos(pool.get_ref()).await?;
-    Ok(HttpResponse::Ok().json(todos))
+pub async fn list_dependencies_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    if database::get_todo(pool.get_ref(), user_id, id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::NotFound(format!("Todo with id {} not found", id)));
+    }
+
+    let blockers = database::list_dependencies(pool.get_ref(), user_id, id).await?;
+    Ok(HttpResponse::Ok().json(blockers))
 }
 
 #[utoipa::path(
-    put,
-    path = "/todos/{id}",
-    request_body = UpdateTodo,
+    post,
+    path = "/api/v1/todos/{id}/dependencies",
+    request_body = AddDependency,
     responses(
-        (status = 200, description = "Todo updated successfully", body = Todo),
-        (status = 404, description = "Todo not found", body = ErrorResponse),
-        (status = 500, description = "Internal server error", body = ErrorResponse),
+        (status = 201, description = "Dependency added; returns all of the todo's blockers", body = [Todo]),
+        (status = 400, description = "Todo cannot depend on itself", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Dependency would create a cycle", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
     ),
     params(
         ("id" = i32, Path, description = "Todo ID")
     ),
     tag = "todos"
 )]
-pub async fn update_todo_handler(
+pub async fn add_dependency_handler(
     pool: web::Data<PgPool>,
     path: web::Path<i32>,
-    todo: web::Json<UpdateTodo>,
+    dependency: web::Json<AddDependency>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
     let id = path.into_inner();
 
-    match database::update_todo(
-        pool.get_ref(),
-        id,
-        todo.title.as_deref(),
-        todo.description.as_deref(),
-        todo.completed,
-        todo.priority.as_ref(),
-    )
-    .await?
+    database::add_dependency(pool.get_ref(), user_id, id, dependency.depends_on_id).await?;
+
+    let blockers = database::list_dependencies(pool.get_ref(), user_id, id).await?;
+    Ok(HttpResponse::Created().json(blockers))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/todos/{id}/dependencies/{depends_on_id}",
+    responses(
+        (status = 204, description = "Dependency removed"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Dependency not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID"),
+        ("depends_on_id" = i32, Path, description = "ID of the blocking todo")
+    ),
+    tag = "todos"
+)]
+pub async fn remove_dependency_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let (id, depends_on_id) = path.into_inner();
+
+    if database::remove_dependency(pool.get_ref(), user_id, id, depends_on_id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound(format!(
+            "Todo {} does not depend on todo {}",
+            id, depends_on_id
+        )))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/{id}/labels",
+    responses(
+        (status = 200, description = "Your labels on the todo", body = [Label]),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "labels"
+)]
+pub async fn list_todo_labels_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    if database::get_todo(pool.get_ref(), user_id, id)
+        .await?
+        .is_none()
     {
-        Some(updated_todo) => Ok(HttpResponse::Ok().json(updated_todo)),
-        None => Err(AppError::NotFound(format!("Todo with id {} not found", id))),
+        return Err(AppError::NotFound(format!("Todo with id {} not found", id)));
     }
+
+    let labels = database::list_todo_labels(pool.get_ref(), user_id, id).await?;
+    Ok(HttpResponse::Ok().json(labels))
 }
 
 #[utoipa::path(
-    delete,
-    path = "/todos/{id}",
+    post,
+    path = "/api/v1/todos/{id}/labels",
+    request_body = AddTodoLabel,
     responses(
-        (status = 204, description = "Todo deleted successfully"),
-        (status = 404, description = "Todo not found", body = ErrorResponse),
-        (status = 500, description = "Internal server error", body = ErrorResponse)
+        (status = 201, description = "Label added; returns all of your labels on the todo", body = [Label]),
+        (status = 404, description = "Todo or label not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
     ),
     params(
         ("id" = i32, Path, description = "Todo ID")
     ),
-    tag = "todos"
+    tag = "labels"
 )]
-pub async fn delete_todo_handler(
+pub async fn add_todo_label_handler(
     pool: web::Data<PgPool>,
     path: web::Path<i32>,
+    label: web::Json<AddTodoLabel>,
+    req: HttpRequest,
 ) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
     let id = path.into_inner();
-    //    // pool.get_ref() converts web::Data<PgPool> -> &PgPool
 
-    if database::delete_todo(pool.get_ref(), id).await? {
+    database::add_todo_label(pool.get_ref(), user_id, id, label.label_id).await?;
+
+    let labels = database::list_todo_labels(pool.get_ref(), user_id, id).await?;
+    Ok(HttpResponse::Created().json(labels))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/todos/{id}/labels/{label_id}",
+    responses(
+        (status = 204, description = "Label removed from the todo"),
+        (status = 404, description = "The todo does not have this label", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID"),
+        ("label_id" = i32, Path, description = "Label ID")
+    ),
+    tag = "labels"
+)]
+pub async fn remove_todo_label_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<(i32, i32)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let (id, label_id) = path.into_inner();
+
+    if database::remove_todo_label(pool.get_ref(), user_id, id, label_id).await? {
         Ok(HttpResponse::NoContent().finish())
     } else {
-        Err(AppError::NotFound(format!("Todo with id {} not found", id)))
+        Err(AppError::NotFound(format!(
+            "Todo {} does not have label {}",
+            id, label_id
+        )))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos",
+    params(TodoListQuery),
+    responses(
+        (status = 200, description = "Page of todos matching the filters", body = TodoPage),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 400, description = "Invalid filter, limit or cursor", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn list_todos_handler(
+    pool: web::Data<PgPool>,
+    cache: web::Data<ListCache>,
+    query: web::Query<TodoListQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let page = todo_page(pool.get_ref(), user_id, query.into_inner(), req.path());
+    cache.page(&req, user_id, page).await
+}
+
+// Shared by GET /todos and GET /lists/{id}/todos; `path` is used for the page links
+async fn todo_page(
+    pool: &PgPool,
+    user_id: Uuid,
+    query: TodoListQuery,
+    path: &str,
+) -> Result<TodoPage, AppError> {
+    let limit = pagination::page_size(query.limit)?;
+    let cursor = query.cursor.as_deref().map(Cursor::decode).transpose()?;
+
+    if cursor.is_some() && query.offset.is_some() {
+        return Err(AppError::BadRequest(
+            "Use either offset or cursor, not both".to_string(),
+        ));
+    }
+    if query.offset.is_some_and(|offset| offset < 0) {
+        return Err(AppError::BadRequest(
+            "offset cannot be negative".to_string(),
+        ));
     }
+
+    // Fetch one extra row to know whether another page exists
+    let mut todos = database::list_todos(pool, user_id, &query, limit + 1, cursor.as_ref()).await?;
+    let has_more = todos.len() as i64 > limit;
+    todos.truncate(limit as usize);
+
+    let total = database::count_todos(pool, user_id, &query).await?;
+
+    let sort = query.sort.unwrap_or_default();
+    let next_cursor = if has_more {
+        todos.last().map(|todo| Cursor::after(todo, sort).encode())
+    } else {
+        None
+    };
+
+    let links = pagination::page_links(path, &query, limit, has_more, next_cursor.as_deref());
+
+    Ok(TodoPage {
+        data: todos,
+        total,
+        limit,
+        offset: if cursor.is_none() {
+            Some(query.offset.unwrap_or(0))
+        } else {
+            None
+        },
+        next_cursor,
+        links,
+    })
 }
 
-pub async fn health_check() -> HttpResponse {
-    HttpResponse::Ok().json(serde_json::json!({
-        "status": "healthy",
-        "service": "Todo API",
-        "version": "1.0.0"
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/search",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Matching todos, best first", body = SearchPage),
+        (status = 400, description = "Empty or invalid query, limit or offset", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn search_todos_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<SearchQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let query = query.into_inner();
+
+    let terms = search::parse(&query.q)?;
+    let limit = pagination::page_size(query.limit)?;
+    let offset = query.offset.unwrap_or(0);
+    if offset < 0 {
+        return Err(AppError::BadRequest(
+            "offset cannot be negative".to_string(),
+        ));
+    }
+
+    let filters = TodoListQuery {
+        completed: query.completed,
+        priority: query.priority,
+        list_id: query.list_id,
+        ..Default::default()
+    };
+
+    let (hits, total) =
+        database::search_todos(pool.get_ref(), user_id, &filters, &terms, limit, offset).await?;
+
+    Ok(HttpResponse::Ok().json(SearchPage {
+        data: hits,
+        total,
+        limit,
+        offset,
     }))
 }
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/export",
+    params(ExportQuery),
+    responses(
+        (status = 200, description = "Todos in id order, streamed as a file download", content(
+            ("text/csv" = String),
+            ("text/calendar" = String),
+            ("application/json" = Vec<Todo>)
+        )),
+        (status = 400, description = "Unknown format", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn export_todos_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<ExportQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let format = query.format;
+
+    Ok(HttpResponse::Ok()
+        .content_type(export::content_type(format))
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", export::file_name(format)),
+        ))
+        .streaming(export::stream(pool.get_ref().clone(), user_id, &query)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/import",
+    params(ImportQuery),
+    request_body(content = String, description = "CSV with a header row. Columns named title, description, completed, priority, due_at, recurrence and list_id are read (use `map` for other names); other columns are ignored.", content_type = "text/csv"),
+    responses(
+        (status = 200, description = "Per-row report; valid rows are saved unless dry_run is set", body = ImportReport),
+        (status = 400, description = "Unreadable CSV, missing title column, bad map or too many rows", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn import_todos_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    let report = import::run(pool.get_ref(), user_id, &body, &query).await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/bulk",
+    request_body = BulkRequest,
+    responses(
+        (status = 200, description = "Changes committed; per-item results (best_effort may include failed items)", body = BulkResponse),
+        (status = 400, description = "Malformed bulk request", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Atomic request rolled back; the last result is the failing item", body = BulkResponse),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn bulk_todos_handler(
+    pool: web::Data<PgPool>,
+    request: web::Json<BulkRequest>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let response = bulk::run(pool.get_ref(), user_id, request.into_inner()).await?;
+
+    if response.committed {
+        Ok(HttpResponse::Ok().json(response))
+    } else {
+        Ok(HttpResponse::UnprocessableEntity().json(response))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/stats",
+    params(StatsQuery),
+    responses(
+        (status = 200, description = "Todo statistics", body = TodoStats),
+        (status = 400, description = "Invalid date range", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn todo_stats_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<StatsQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    if let (Some(from), Some(to)) = (query.from, query.to)
+        && from >= to
+    {
+        return Err(AppError::BadRequest("from must be before to".to_string()));
+    }
+
+    let stats = database::todo_stats(pool.get_ref(), user_id, &query).await?;
+    Ok(HttpResponse::Ok().json(stats))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/overdue",
+    responses(
+        (status = 200, description = "Open todos past their due date, oldest first", body = [Todo]),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn overdue_todos_handler(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let todos = database::list_overdue_todos(pool.get_ref(), user_id).await?;
+    Ok(HttpResponse::Ok().json(todos))
+}
+
+// Parses windows like "90m", "24h", "7d" or "2w"
+fn parse_window(within: &str) -> Result<chrono::Duration, AppError> {
+    let invalid = || {
+        AppError::BadRequest(format!(
+            "Invalid within '{}', expected e.g. 90m, 24h, 7d or 2w (max 365d)",
+            within
+        ))
+    };
+
+    let split = within.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = within.split_at(split);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+
+    let window = match unit {
+        "m" => chrono::Duration::minutes(amount),
+        "h" => chrono::Duration::hours(amount),
+        "d" => chrono::Duration::days(amount),
+        "w" => chrono::Duration::weeks(amount),
+        _ => return Err(invalid()),
+    };
+
+    if amount <= 0 || window > chrono::Duration::days(365) {
+        return Err(invalid());
+    }
+
+    Ok(window)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/upcoming",
+    params(UpcomingQuery),
+    responses(
+        (status = 200, description = "Open todos due within the window, soonest first", body = [Todo]),
+        (status = 400, description = "Invalid window", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn upcoming_todos_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<UpcomingQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let within = parse_window(query.within.as_deref().unwrap_or("24h"))?;
+    let todos = database::list_upcoming_todos(pool.get_ref(), user_id, within).await?;
+    Ok(HttpResponse::Ok().json(todos))
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/todos/{id}",
+    request_body(content = UpdateTodo, description = "JSON Merge Patch (application/json or application/merge-patch+json): omitted fields are unchanged, null clears description, due_at, recurrence, parent_id and list_id and is rejected for title, completed and priority"),
+    responses(
+        (status = 200, description = "Todo updated successfully", body = Todo),
+        (status = 400, description = "Invalid recurrence rule or parent", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Todo has open blockers, the new parent is one of its subtasks, or its list is archived", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Empty or too long title, or null title, completed or priority", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json"),
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "todos"
+)]
+pub async fn update_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    todo: web::Json<UpdateTodo>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+    let recurrence = todo
+        .recurrence_rule()
+        .map(str::parse::<Recurrence>)
+        .transpose()?;
+
+    match database::update_todo(pool.get_ref(), user_id, id, &todo, recurrence.as_ref()).await? {
+        Some(updated_todo) => Ok(HttpResponse::Ok().json(updated_todo)),
+        None => Err(AppError::NotFound(format!("Todo with id {} not found", id))),
+    }
+}
+
+// PUT predates PATCH and is kept for older clients. It is documented as its
+// own operation, since a route can only be described by one utoipa::path.
+#[utoipa::path(
+    put,
+    path = "/api/v1/todos/{id}",
+    request_body(content = UpdateTodo, description = "Same as PATCH: a JSON Merge Patch, not a full replacement"),
+    responses(
+        (status = 200, description = "Todo updated successfully", body = Todo),
+        (status = 400, description = "Invalid recurrence rule or parent", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Todo has open blockers, the new parent is one of its subtasks, or its list is archived", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Empty or too long title, or null title, completed or priority", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json"),
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "todos"
+)]
+pub async fn put_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    todo: web::Json<UpdateTodo>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    update_todo_handler(pool, path, todo, req).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/todos/{id}",
+    responses(
+        (status = 204, description = "Todo deleted successfully"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Todo is in an archived list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "todos"
+)]
+pub async fn delete_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+    //    // pool.get_ref() converts web::Data<PgPool> -> &PgPool
+
+    if database::delete_todo(pool.get_ref(), user_id, id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound(format!("Todo with id {} not found", id)))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/{id}/archive",
+    responses(
+        (status = 200, description = "Todo is archived (already archived todos are returned unchanged)", body = Todo),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Todo is in an archived list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "todos"
+)]
+pub async fn archive_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let todo = database::set_archived(pool.get_ref(), user_id, path.into_inner(), true).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/{id}/unarchive",
+    responses(
+        (status = 200, description = "Todo is no longer archived", body = Todo),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Todo is in an archived list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "todos"
+)]
+pub async fn unarchive_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let todo = database::set_archived(pool.get_ref(), user_id, path.into_inner(), false).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/todos/{id}/move",
+    request_body = MoveTodo,
+    responses(
+        (status = 200, description = "Todo with its new position", body = Todo),
+        (status = 400, description = "No neighbor given, or a neighbor is not in the todo's list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Todo is in an archived list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID")
+    ),
+    tag = "todos"
+)]
+pub async fn move_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    target: web::Json<MoveTodo>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let todo = database::move_todo(pool.get_ref(), user_id, path.into_inner(), &target).await?;
+    Ok(HttpResponse::Ok().json(todo))
+}
+
+// Events are fetched with one extra row to tell whether an older page exists
+fn event_page(mut events: Vec<TodoEvent>, limit: i64) -> EventPage {
+    let has_more = events.len() as i64 > limit;
+    events.truncate(limit as usize);
+
+    EventPage {
+        next_before: if has_more {
+            events.last().map(|event| event.id)
+        } else {
+            None
+        },
+        data: events,
+        limit,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/todos/{id}/history",
+    responses(
+        (status = 200, description = "Changes to the todo, newest first; still available after it is deleted", body = EventPage),
+        (status = 400, description = "Invalid limit", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Todo not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Todo ID"),
+        HistoryQuery
+    ),
+    tag = "todos"
+)]
+pub async fn todo_history_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    query: web::Query<HistoryQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+    let limit = pagination::page_size(query.limit)?;
+
+    let events = database::list_events(
+        pool.get_ref(),
+        user_id,
+        Some(id),
+        None,
+        query.before,
+        limit + 1,
+    )
+    .await?;
+
+    // No visible events: an empty page for a todo the caller can see, 404 otherwise
+    if events.is_empty()
+        && query.before.is_none()
+        && database::get_todo(pool.get_ref(), user_id, id)
+            .await?
+            .is_none()
+    {
+        return Err(AppError::NotFound(format!("Todo with id {} not found", id)));
+    }
+
+    Ok(HttpResponse::Ok().json(event_page(events, limit)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/activity",
+    params(ActivityQuery),
+    responses(
+        (status = 200, description = "Changes to every todo the caller can see, newest first", body = EventPage),
+        (status = 400, description = "Invalid limit", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "todos"
+)]
+pub async fn activity_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<ActivityQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let limit = pagination::page_size(query.limit)?;
+
+    let events = database::list_events(
+        pool.get_ref(),
+        user_id,
+        None,
+        query.list_id,
+        query.before,
+        limit + 1,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(event_page(events, limit)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists",
+    params(ListsQuery),
+    responses(
+        (status = 200, description = "Lists the caller is a member of, with their role", body = [List]),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "lists"
+)]
+pub async fn list_lists_handler(
+    pool: web::Data<PgPool>,
+    query: web::Query<ListsQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let archived = query.archived.unwrap_or(false);
+    let lists = database::list_lists(pool.get_ref(), user_id, archived).await?;
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists",
+    request_body = CreateList,
+    responses(
+        (status = 201, description = "List created; the caller is its owner", body = List),
+        (status = 400, description = "Empty name", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 401, description = "Missing or invalid token", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "lists"
+)]
+pub async fn create_list_handler(
+    pool: web::Data<PgPool>,
+    list: web::Json<CreateList>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    let name = list.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "List name cannot be empty".to_string(),
+        ));
+    }
+
+    let list = database::create_list(pool.get_ref(), user_id, name).await?;
+    Ok(HttpResponse::Created().json(list))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists/{id}",
+    responses(
+        (status = 200, description = "List found", body = List),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn get_list_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    match database::get_list(pool.get_ref(), user_id, id).await? {
+        Some(list) => Ok(HttpResponse::Ok().json(list)),
+        None => Err(AppError::NotFound(format!("List with id {} not found", id))),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/lists/{id}",
+    request_body = UpdateList,
+    responses(
+        (status = 200, description = "List renamed", body = List),
+        (status = 400, description = "Empty name", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "List is archived", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn update_list_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    list: web::Json<UpdateList>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    let name = list.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest(
+            "List name cannot be empty".to_string(),
+        ));
+    }
+
+    let list = database::rename_list(pool.get_ref(), user_id, path.into_inner(), name).await?;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/lists/{id}",
+    responses(
+        (status = 204, description = "List archived, or deleted with its todos"),
+        (status = 403, description = "Only owners can delete a list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID"),
+        DeleteListQuery
+    ),
+    tag = "lists"
+)]
+pub async fn delete_list_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    query: web::Query<DeleteListQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let mode = query.mode.unwrap_or_default();
+
+    database::delete_list(pool.get_ref(), user_id, path.into_inner(), mode).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists/{id}/restore",
+    responses(
+        (status = 200, description = "List is active again", body = List),
+        (status = 403, description = "Only owners can restore a list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn restore_list_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let list = database::restore_list(pool.get_ref(), user_id, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(list))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists/{id}/todos",
+    params(
+        ("id" = i32, Path, description = "List ID"),
+        TodoListQuery
+    ),
+    responses(
+        (status = 200, description = "Page of todos in the list, by position unless another sort is given", body = TodoPage),
+        (status = 304, description = "Unchanged since the ETag in If-None-Match"),
+        (status = 400, description = "Invalid filter, limit or cursor", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "lists"
+)]
+pub async fn list_list_todos_handler(
+    pool: web::Data<PgPool>,
+    cache: web::Data<ListCache>,
+    path: web::Path<i32>,
+    query: web::Query<TodoListQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let list_id = path.into_inner();
+
+    if database::get_list(pool.get_ref(), user_id, list_id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::NotFound(format!(
+            "List with id {} not found",
+            list_id
+        )));
+    }
+
+    let mut query = query.into_inner();
+    query.list_id = Some(list_id);
+    if query.sort.is_none() {
+        query.sort = Some(SortField::Position);
+        query.order = query.order.or(Some(SortOrder::Asc));
+    }
+
+    let page = todo_page(pool.get_ref(), user_id, query, req.path());
+    cache.page(&req, user_id, page).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists/{id}/todos",
+    request_body = CreateTodo,
+    responses(
+        (status = 201, description = "Todo created at the end of the list", body = Todo),
+        (status = 400, description = "Invalid recurrence rule or parent", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access to the list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List or parent not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "List is archived", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Empty or too long title", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn create_list_todo_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    todo: web::Json<CreateTodo>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let mut todo = todo.into_inner();
+    todo.list_id = Some(path.into_inner());
+
+    let recurrence = todo
+        .recurrence
+        .as_deref()
+        .map(str::parse::<Recurrence>)
+        .transpose()?;
+
+    let created_todo =
+        database::create_todo(pool.get_ref(), user_id, &todo, recurrence.as_ref()).await?;
+
+    Ok(HttpResponse::Created().json(created_todo))
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/lists/{id}/todos/order",
+    request_body = ReorderTodos,
+    responses(
+        (status = 200, description = "Todos in their new order", body = [Todo]),
+        (status = 400, description = "todo_ids is not exactly the todos in the list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 403, description = "Read-only access to the list", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "List is archived", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn reorder_list_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    order: web::Json<ReorderTodos>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    let todos =
+        database::reorder_list(pool.get_ref(), user_id, path.into_inner(), &order.todo_ids).await?;
+    Ok(HttpResponse::Ok().json(todos))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/lists/{id}/members",
+    responses(
+        (status = 200, description = "Members of the list", body = [ListMember]),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn list_members_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let members = database::list_members(pool.get_ref(), user_id, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(members))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/lists/{id}/members",
+    request_body = AddListMember,
+    responses(
+        (status = 200, description = "Member added or role changed", body = ListMember),
+        (status = 403, description = "Only owners can manage members", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List or user not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The list would be left without an owner", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID")
+    ),
+    tag = "lists"
+)]
+pub async fn add_list_member_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    member: web::Json<AddListMember>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    let member = database::upsert_list_member(
+        pool.get_ref(),
+        user_id,
+        path.into_inner(),
+        &member.email.trim().to_lowercase(),
+        member.role,
+    )
+    .await?;
+
+    Ok(HttpResponse::Ok().json(member))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/lists/{id}/members/{user_id}",
+    responses(
+        (status = 204, description = "Member removed"),
+        (status = 403, description = "Only owners can remove other members", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List or member not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "The list would be left without an owner", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "List ID"),
+        ("user_id" = Uuid, Path, description = "Member to remove (may be the caller)")
+    ),
+    tag = "lists"
+)]
+pub async fn remove_list_member_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<(i32, Uuid)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let (list_id, member_id) = path.into_inner();
+
+    if database::remove_list_member(pool.get_ref(), user_id, list_id, member_id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound(format!(
+            "User {} is not a member of list {}",
+            member_id, list_id
+        )))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/labels",
+    responses(
+        (status = 200, description = "Your labels, by name", body = [Label]),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "labels"
+)]
+pub async fn list_labels_handler(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let labels = database::list_labels(pool.get_ref(), user_id).await?;
+    Ok(HttpResponse::Ok().json(labels))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/labels",
+    request_body = CreateLabel,
+    responses(
+        (status = 201, description = "Label created", body = Label),
+        (status = 409, description = "You already have a label with this name", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Empty or too long name, or invalid color", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "labels"
+)]
+pub async fn create_label_handler(
+    pool: web::Data<PgPool>,
+    label: web::Json<CreateLabel>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    label.validate()?;
+
+    let created = database::create_label(pool.get_ref(), user_id, &label).await?;
+    Ok(HttpResponse::Created().json(created))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/labels/{id}",
+    responses(
+        (status = 200, description = "Label found", body = Label),
+        (status = 404, description = "Label not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Label ID")
+    ),
+    tag = "labels"
+)]
+pub async fn get_label_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    match database::get_label(pool.get_ref(), user_id, id).await? {
+        Some(label) => Ok(HttpResponse::Ok().json(label)),
+        None => Err(AppError::NotFound(format!(
+            "Label with id {} not found",
+            id
+        ))),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/labels/{id}",
+    request_body = UpdateLabel,
+    responses(
+        (status = 200, description = "Label updated", body = Label),
+        (status = 404, description = "Label not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "You already have a label with this name", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 422, description = "Empty or too long name, or invalid color", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Label ID")
+    ),
+    tag = "labels"
+)]
+pub async fn update_label_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    changes: web::Json<UpdateLabel>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+    changes.validate()?;
+
+    match database::update_label(pool.get_ref(), user_id, id, &changes).await? {
+        Some(label) => Ok(HttpResponse::Ok().json(label)),
+        None => Err(AppError::NotFound(format!(
+            "Label with id {} not found",
+            id
+        ))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/labels/{id}",
+    responses(
+        (status = 204, description = "Label deleted and removed from its todos"),
+        (status = 404, description = "Label not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Label ID")
+    ),
+    tag = "labels"
+)]
+pub async fn delete_label_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    if database::delete_label(pool.get_ref(), user_id, id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound(format!(
+            "Label with id {} not found",
+            id
+        )))
+    }
+}
+
+// The list must be visible to the caller; events are further limited to
+// todos the caller can see when they happen
+async fn check_webhook_list(
+    pool: &PgPool,
+    user_id: Uuid,
+    list_id: Option<i32>,
+) -> Result<(), AppError> {
+    if let Some(list_id) = list_id
+        && database::get_list(pool, user_id, list_id).await?.is_none()
+    {
+        return Err(AppError::NotFound(format!(
+            "List with id {} not found",
+            list_id
+        )));
+    }
+
+    Ok(())
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks",
+    responses(
+        (status = 200, description = "Your webhooks", body = [Webhook]),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "webhooks"
+)]
+pub async fn list_webhooks_handler(
+    pool: web::Data<PgPool>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    let hooks = database::list_webhooks(pool.get_ref(), user_id).await?;
+
+    Ok(HttpResponse::Ok().json(hooks))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks",
+    request_body = CreateWebhook,
+    responses(
+        (status = 201, description = "Webhook created; the response includes its signing secret", body = Webhook),
+        (status = 400, description = "Invalid url or events", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "List not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    tag = "webhooks"
+)]
+pub async fn create_webhook_handler(
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+    webhook: web::Json<CreateWebhook>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+
+    webhooks::validate_url(&webhook.url, settings.allow_private_targets).await?;
+    webhooks::validate_events(&webhook.events)?;
+    check_webhook_list(pool.get_ref(), user_id, webhook.list_id).await?;
+
+    let created =
+        database::create_webhook(pool.get_ref(), user_id, &webhook, &webhooks::new_secret())
+            .await?;
+
+    Ok(HttpResponse::Created().json(created))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}",
+    responses(
+        (status = 200, description = "Webhook found", body = Webhook),
+        (status = 404, description = "Webhook not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Webhook ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn get_webhook_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    match database::get_webhook(pool.get_ref(), user_id, id).await? {
+        Some(webhook) => Ok(HttpResponse::Ok().json(webhook)),
+        None => Err(AppError::NotFound(format!(
+            "Webhook with id {} not found",
+            id
+        ))),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/webhooks/{id}",
+    request_body = UpdateWebhook,
+    responses(
+        (status = 200, description = "Webhook updated; includes the new secret when it was rotated", body = Webhook),
+        (status = 400, description = "Invalid url or events", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook or list not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Webhook ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn update_webhook_handler(
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+    path: web::Path<i32>,
+    changes: web::Json<UpdateWebhook>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    if let Some(url) = &changes.url {
+        webhooks::validate_url(url, settings.allow_private_targets).await?;
+    }
+    if let Some(events) = &changes.events {
+        webhooks::validate_events(events)?;
+    }
+    check_webhook_list(pool.get_ref(), user_id, changes.list_id.flatten()).await?;
+
+    let new_secret = changes
+        .rotate_secret
+        .unwrap_or(false)
+        .then(webhooks::new_secret);
+
+    match database::update_webhook(pool.get_ref(), user_id, id, &changes, new_secret.as_deref())
+        .await?
+    {
+        Some(webhook) => Ok(HttpResponse::Ok().json(webhook)),
+        None => Err(AppError::NotFound(format!(
+            "Webhook with id {} not found",
+            id
+        ))),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/webhooks/{id}",
+    responses(
+        (status = 204, description = "Webhook and its deliveries deleted"),
+        (status = 404, description = "Webhook not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Webhook ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn delete_webhook_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+
+    if database::delete_webhook(pool.get_ref(), user_id, id).await? {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Err(AppError::NotFound(format!(
+            "Webhook with id {} not found",
+            id
+        )))
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/webhooks/{id}/deliveries",
+    responses(
+        (status = 200, description = "Deliveries for the webhook, newest first", body = DeliveryPage),
+        (status = 400, description = "Invalid limit", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 404, description = "Webhook not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Webhook ID"),
+        DeliveriesQuery
+    ),
+    tag = "webhooks"
+)]
+pub async fn list_deliveries_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<i32>,
+    query: web::Query<DeliveriesQuery>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let id = path.into_inner();
+    let limit = pagination::page_size(query.limit)?;
+
+    if database::get_webhook(pool.get_ref(), user_id, id)
+        .await?
+        .is_none()
+    {
+        return Err(AppError::NotFound(format!(
+            "Webhook with id {} not found",
+            id
+        )));
+    }
+
+    let mut deliveries = database::list_deliveries(
+        pool.get_ref(),
+        user_id,
+        id,
+        query.status,
+        query.before,
+        limit + 1,
+    )
+    .await?;
+
+    // One extra row tells whether an older page exists
+    let has_more = deliveries.len() as i64 > limit;
+    deliveries.truncate(limit as usize);
+
+    Ok(HttpResponse::Ok().json(DeliveryPage {
+        next_before: if has_more {
+            deliveries.last().map(|delivery| delivery.id)
+        } else {
+            None
+        },
+        data: deliveries,
+        limit,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/webhooks/{id}/deliveries/{delivery_id}/retry",
+    responses(
+        (status = 200, description = "Delivery queued again with a fresh set of attempts", body = WebhookDelivery),
+        (status = 404, description = "Webhook or delivery not found", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 409, description = "Delivery is still pending", body = ErrorResponse, content_type = "application/problem+json"),
+        (status = 500, description = "Internal server error", body = ErrorResponse, content_type = "application/problem+json")
+    ),
+    params(
+        ("id" = i32, Path, description = "Webhook ID"),
+        ("delivery_id" = i64, Path, description = "Delivery ID")
+    ),
+    tag = "webhooks"
+)]
+pub async fn retry_delivery_handler(
+    pool: web::Data<PgPool>,
+    path: web::Path<(i32, i64)>,
+    req: HttpRequest,
+) -> Result<HttpResponse, AppError> {
+    let user_id = auth::user_id(&req)?;
+    let (id, delivery_id) = path.into_inner();
+
+    match database::retry_delivery(pool.get_ref(), user_id, id, delivery_id).await? {
+        Some(delivery) => Ok(HttpResponse::Ok().json(delivery)),
+        None => Err(AppError::NotFound(format!(
+            "Delivery with id {} not found",
+            delivery_id
+        ))),
+    }
+}
+
+const READY_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+fn health_response(
+    database: &str,
+    error: Option<String>,
+    pool: Option<PoolStats>,
+    cache: Option<CacheStats>,
+) -> HealthResponse {
+    HealthResponse {
+        status: if error.is_none() {
+            "healthy"
+        } else {
+            "degraded"
+        }
+        .to_string(),
+        service: "Todo API".to_string(),
+        version: "1.0.0".to_string(),
+        database: database.to_string(),
+        error,
+        pool,
+        cache,
+    }
+}
+
+// Liveness: the process is serving requests and the pool has not been closed
+#[utoipa::path(
+    get,
+    path = "/health/live",
+    responses(
+        (status = 200, description = "The service is up", body = HealthResponse),
+        (status = 503, description = "The connection pool is closed", body = HealthResponse)
+    ),
+    security(()),
+    tag = "health"
+)]
+pub async fn health_live(pool: web::Data<PgPool>) -> HttpResponse {
+    if pool.is_closed() {
+        let response = health_response(
+            "closed",
+            Some("Connection pool is closed".to_string()),
+            None,
+            None,
+        );
+        return HttpResponse::ServiceUnavailable().json(response);
+    }
+
+    HttpResponse::Ok().json(health_response("not_checked", None, None, None))
+}
+
+// Readiness: Postgres answers SELECT 1 within READY_CHECK_TIMEOUT. Also
+// reports the pool and list cache counters.
+#[utoipa::path(
+    get,
+    path = "/health/ready",
+    responses(
+        (status = 200, description = "The database answers", body = HealthResponse),
+        (status = 503, description = "The database is down or too slow", body = HealthResponse)
+    ),
+    security(()),
+    tag = "health"
+)]
+pub async fn health_ready(pool: web::Data<PgPool>, cache: web::Data<ListCache>) -> HttpResponse {
+    let stats = Some(database::pool_stats(pool.get_ref()));
+    let cache_stats = Some(cache.stats());
+
+    match database::ping(pool.get_ref(), READY_CHECK_TIMEOUT).await {
+        Ok(()) => HttpResponse::Ok().json(health_response("up", None, stats, cache_stats)),
+        Err(e) => HttpResponse::ServiceUnavailable().json(health_response(
+            "down",
+            Some(e.to_string()),
+            stats,
+            cache_stats,
+        )),
+    }
+}
 // as_ref(): Option<T> → Option<&T> (borrow the value)
 // as_deref(): Option<String> → Option<&str> (borrow the dereferenced content)
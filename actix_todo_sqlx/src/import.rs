@@ -0,0 +1,559 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Acquire, PgConnection, PgPool};
+use uuid::Uuid;
+
+use crate::database;
+use crate::errors::AppError;
+use crate::export;
+use crate::models::{CreateTodo, ImportQuery, ImportReport, ImportRowError, Priority, UpdateTodo};
+use crate::recurrence::Recurrence;
+
+pub const MAX_IMPORT_ROWS: usize = 10_000;
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Description,
+    Completed,
+    Priority,
+    DueAt,
+    Recurrence,
+    ListId,
+}
+
+impl Field {
+    fn name(self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::Description => "description",
+            Field::Completed => "completed",
+            Field::Priority => "priority",
+            Field::DueAt => "due_at",
+            Field::Recurrence => "recurrence",
+            Field::ListId => "list_id",
+        }
+    }
+
+    // "Due At", "due-at" and "due_at" all name the due_at field
+    fn from_name(name: &str) -> Option<Field> {
+        let name = name.trim().to_ascii_lowercase().replace([' ', '-'], "_");
+        match name.as_str() {
+            "title" => Some(Field::Title),
+            "description" => Some(Field::Description),
+            "completed" => Some(Field::Completed),
+            "priority" => Some(Field::Priority),
+            "due_at" => Some(Field::DueAt),
+            "recurrence" => Some(Field::Recurrence),
+            "list_id" => Some(Field::ListId),
+            _ => None,
+        }
+    }
+}
+
+// Splits RFC 4180 CSV into records. Quoted fields may contain separators,
+// doubled quotes and line breaks; both CRLF and LF end a record.
+fn parse_csv(text: &str) -> Result<Vec<Vec<String>>, AppError> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(AppError::BadRequest(
+            "CSV ends inside a quoted field".to_string(),
+        ));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    Ok(records)
+}
+
+// `map` pairs headers with fields: "Task:title,Notes:description"
+fn parse_mapping(map: Option<&str>) -> Result<Vec<(String, Field)>, AppError> {
+    let Some(map) = map else {
+        return Ok(Vec::new());
+    };
+
+    map.split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (header, field) = pair.rsplit_once(':').ok_or_else(|| {
+                AppError::BadRequest(format!("map entry '{}' is not Header:field", pair))
+            })?;
+            let field = Field::from_name(field).ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "map entry '{}': unknown field '{}' (expected title, description, completed, priority, due_at, recurrence or list_id)",
+                    pair, field
+                ))
+            })?;
+            Ok((header.trim().to_lowercase(), field))
+        })
+        .collect()
+}
+
+// The field read from each column, or None for an ignored column
+fn map_columns(
+    headers: &[String],
+    mapping: &[(String, Field)],
+) -> Result<Vec<Option<Field>>, AppError> {
+    let columns: Vec<Option<Field>> = headers
+        .iter()
+        .map(|header| {
+            let key = header.trim().to_lowercase();
+            match mapping.iter().find(|(mapped, _)| *mapped == key) {
+                Some((_, field)) => Some(*field),
+                None => Field::from_name(header),
+            }
+        })
+        .collect();
+
+    if let Some((header, _)) = mapping
+        .iter()
+        .find(|(mapped, _)| !headers.iter().any(|h| h.trim().to_lowercase() == *mapped))
+    {
+        return Err(AppError::BadRequest(format!(
+            "map refers to '{}', which is not a column",
+            header
+        )));
+    }
+    for (i, field) in columns.iter().enumerate() {
+        if let Some(field) = field
+            && columns[..i].contains(&Some(*field))
+        {
+            return Err(AppError::BadRequest(format!(
+                "More than one column maps to {}",
+                field.name()
+            )));
+        }
+    }
+    if !columns.contains(&Some(Field::Title)) {
+        return Err(AppError::BadRequest(
+            "CSV needs a title column (use map to name it)".to_string(),
+        ));
+    }
+
+    Ok(columns)
+}
+
+fn parse_bool(value: &str) -> Result<bool, AppError> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "y" | "1" | "x" => Ok(true),
+        "false" | "no" | "n" | "0" => Ok(false),
+        _ => Err(AppError::BadRequest(format!(
+            "completed must be true or false, not '{}'",
+            value
+        ))),
+    }
+}
+
+// RFC 3339 timestamps, or plain dates (taken as midnight UTC)
+fn parse_due_at(value: &str) -> Result<DateTime<Utc>, AppError> {
+    if let Ok(due_at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(due_at.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .map(|midnight| midnight.and_utc())
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "due_at must be an RFC 3339 timestamp or a YYYY-MM-DD date, not '{}'",
+                value
+            ))
+        })
+}
+
+struct Row {
+    todo: CreateTodo,
+    completed: bool,
+    recurrence: Option<Recurrence>,
+}
+
+fn parse_row(
+    columns: &[Option<Field>],
+    record: &[String],
+    default_list_id: Option<i32>,
+) -> Result<Row, AppError> {
+    if record.len() != columns.len() {
+        return Err(AppError::BadRequest(format!(
+            "Expected {} fields, found {}",
+            columns.len(),
+            record.len()
+        )));
+    }
+
+    let mut todo = CreateTodo {
+        title: String::new(),
+        description: None,
+        priority: None,
+        due_at: None,
+        recurrence: None,
+        parent_id: None,
+        list_id: default_list_id,
+    };
+    let mut completed = false;
+
+    for (field, value) in columns.iter().zip(record) {
+        let value = value.trim();
+        let Some(field) = field else {
+            continue;
+        };
+        // Empty cells leave the field unset
+        if value.is_empty() {
+            continue;
+        }
+
+        match field {
+            Field::Title => todo.title = export::unescape_formula(value).to_string(),
+            Field::Description => {
+                todo.description = Some(export::unescape_formula(value).to_string())
+            }
+            Field::Completed => completed = parse_bool(value)?,
+            Field::Priority => {
+                todo.priority = Some(Priority::from_name(value).ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "priority must be low, medium, high or urgent, not '{}'",
+                        value
+                    ))
+                })?);
+            }
+            Field::DueAt => todo.due_at = Some(parse_due_at(value)?),
+            Field::Recurrence => todo.recurrence = Some(value.to_string()),
+            Field::ListId => {
+                todo.list_id = Some(value.parse().map_err(|_| {
+                    AppError::BadRequest(format!("list_id must be a number, not '{}'", value))
+                })?);
+            }
+        }
+    }
+
+    todo.validate()?;
+
+    // Completing a recurring todo hands its rule on to the next occurrence,
+    // so completed rows are imported without one
+    if completed {
+        todo.recurrence = None;
+    }
+    let recurrence = todo
+        .recurrence
+        .as_deref()
+        .map(str::parse::<Recurrence>)
+        .transpose()?;
+
+    Ok(Row {
+        todo,
+        completed,
+        recurrence,
+    })
+}
+
+// A completed row is created and then completed through update_todo on
+// purpose: the audit log and webhooks see todo.created followed by
+// todo.updated and todo.completed, the same as a todo completed through the
+// API, and completed_at is set to the import time. A dry run rolls these
+// events back with everything else.
+async fn save(conn: &mut PgConnection, user_id: Uuid, row: &Row) -> Result<(), AppError> {
+    let todo =
+        database::create_todo(&mut *conn, user_id, &row.todo, row.recurrence.as_ref()).await?;
+
+    if row.completed {
+        let changes = UpdateTodo {
+            completed: Some(Some(true)),
+            ..Default::default()
+        };
+        database::update_todo(&mut *conn, user_id, todo.id, &changes, None).await?;
+    }
+
+    Ok(())
+}
+
+// Rows are saved independently: a bad row is reported and skipped. A dry run
+// goes through the same steps (including permission checks) and then rolls
+// everything back.
+pub async fn run(
+    pool: &PgPool,
+    user_id: Uuid,
+    body: &[u8],
+    query: &ImportQuery,
+) -> Result<ImportReport, AppError> {
+    let db_error = |e: sqlx::Error| AppError::InternalError(format!("Database error: {}", e));
+
+    let text = std::str::from_utf8(body)
+        .map_err(|_| AppError::BadRequest("CSV must be UTF-8".to_string()))?;
+    let mut records = parse_csv(text)?.into_iter();
+
+    let headers = records
+        .next()
+        .ok_or_else(|| AppError::BadRequest("CSV is empty".to_string()))?;
+    let mapping = parse_mapping(query.map.as_deref())?;
+    let columns = map_columns(&headers, &mapping)?;
+
+    // Row numbers count the header as row 1, like a spreadsheet; blank lines
+    // keep their number but are skipped
+    let rows: Vec<(usize, Vec<String>)> = records
+        .enumerate()
+        .map(|(i, record)| (i + 2, record))
+        .filter(|(_, record)| !(record.len() == 1 && record[0].trim().is_empty()))
+        .collect();
+    if rows.len() > MAX_IMPORT_ROWS {
+        return Err(AppError::BadRequest(format!(
+            "An import can have at most {} rows",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    let dry_run = query.dry_run.unwrap_or(false);
+    let mut tx = pool.begin().await.map_err(db_error)?;
+    let mut errors = Vec::new();
+
+    for (row_number, record) in &rows {
+        let outcome = match parse_row(&columns, record, query.list_id) {
+            Ok(row) => {
+                let mut savepoint = (&mut tx).begin().await.map_err(db_error)?;
+                let outcome = save(&mut savepoint, user_id, &row).await;
+                if outcome.is_ok() {
+                    savepoint.commit().await.map_err(db_error)?;
+                } else {
+                    savepoint.rollback().await.map_err(db_error)?;
+                }
+                outcome
+            }
+            Err(e) => Err(e),
+        };
+
+        match outcome {
+            Ok(()) => {}
+            Err(e @ AppError::InternalError(_)) => return Err(e),
+            Err(e) => {
+                errors.push(ImportRowError {
+                    row: *row_number,
+                    error: e.to_response(),
+                });
+            }
+        }
+    }
+
+    if dry_run {
+        tx.rollback().await.map_err(db_error)?;
+    } else {
+        tx.commit().await.map_err(db_error)?;
+    }
+
+    let ignored_columns = headers
+        .iter()
+        .zip(&columns)
+        .filter(|(_, field)| field.is_none())
+        .map(|(header, _)| header.clone())
+        .collect();
+
+    Ok(ImportReport {
+        dry_run,
+        rows: rows.len(),
+        imported: rows.len() - errors.len(),
+        failed: errors.len(),
+        ignored_columns,
+        errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(text: &str) -> Vec<Vec<String>> {
+        parse_csv(text).unwrap()
+    }
+
+    fn headers(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn bad_request(result: Result<impl std::fmt::Debug, AppError>) -> String {
+        match result {
+            Err(AppError::BadRequest(msg)) => msg,
+            other => panic!("expected a bad request, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn quoted_fields_keep_commas_quotes_and_line_breaks() {
+        assert_eq!(
+            records("title,description\n\"Buy milk, eggs\",\"Say \"\"hi\"\"\nthen leave\"\n"),
+            vec![
+                vec!["title", "description"],
+                vec!["Buy milk, eggs", "Say \"hi\"\nthen leave"],
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_and_lf_end_records_alike() {
+        let lf = records("title,completed\na,true\nb,false\n");
+        let crlf = records("title,completed\r\na,true\r\nb,false\r\n");
+        assert_eq!(lf, crlf);
+        assert_eq!(lf.len(), 3);
+
+        // A missing final line break still ends the last record
+        assert_eq!(records("title\na"), vec![vec!["title"], vec!["a"]]);
+        // CRLF inside quotes is kept as is
+        assert_eq!(records("\"a\r\nb\""), vec![vec!["a\r\nb"]]);
+    }
+
+    #[test]
+    fn byte_order_mark_is_skipped() {
+        assert_eq!(
+            records("\u{feff}title\nWater plants\n"),
+            vec![vec!["title"], vec!["Water plants"]]
+        );
+    }
+
+    #[test]
+    fn empty_fields_are_kept() {
+        assert_eq!(records("a,,\n"), vec![vec!["a", "", ""]]);
+    }
+
+    #[test]
+    fn unterminated_quote_is_rejected() {
+        assert!(bad_request(parse_csv("title\n\"never closed\n")).contains("quoted field"));
+    }
+
+    #[test]
+    fn mapping_assigns_headers_to_fields() {
+        let mapping = parse_mapping(Some("Task:title, Notes:Description,When:due-at")).unwrap();
+        let columns = map_columns(&headers(&["task", "NOTES", "When", "Owner"]), &mapping).unwrap();
+        assert_eq!(
+            columns,
+            vec![
+                Some(Field::Title),
+                Some(Field::Description),
+                Some(Field::DueAt),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn headers_name_fields_without_a_mapping() {
+        let columns = map_columns(&headers(&["Title", "Due At", "list-id", "extra"]), &[]).unwrap();
+        assert_eq!(
+            columns,
+            vec![
+                Some(Field::Title),
+                Some(Field::DueAt),
+                Some(Field::ListId),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn bad_mapping_entries_are_rejected() {
+        assert!(bad_request(parse_mapping(Some("Task"))).contains("Header:field"));
+        assert!(bad_request(parse_mapping(Some("Task:owner"))).contains("unknown field"));
+    }
+
+    #[test]
+    fn mapping_a_missing_column_is_rejected() {
+        let mapping = parse_mapping(Some("Task:title")).unwrap();
+        let msg = bad_request(map_columns(&headers(&["title"]), &mapping));
+        assert!(msg.contains("'task'"), "{}", msg);
+    }
+
+    #[test]
+    fn two_columns_for_one_field_are_rejected() {
+        let mapping = parse_mapping(Some("Task:title")).unwrap();
+        let msg = bad_request(map_columns(&headers(&["Task", "title"]), &mapping));
+        assert!(msg.contains("title"), "{}", msg);
+
+        let mapping = parse_mapping(Some("A:description,B:description")).unwrap();
+        let msg = bad_request(map_columns(&headers(&["title", "A", "B"]), &mapping));
+        assert!(msg.contains("description"), "{}", msg);
+    }
+
+    #[test]
+    fn title_column_is_required() {
+        assert!(bad_request(map_columns(&headers(&["description"]), &[])).contains("title"));
+    }
+
+    #[test]
+    fn rows_are_parsed_into_todos() {
+        let columns = map_columns(
+            &headers(&["title", "completed", "priority", "due_at", "recurrence"]),
+            &[],
+        )
+        .unwrap();
+
+        let row = parse_row(
+            &columns,
+            &headers(&["Pay rent", "", "HIGH", "2027-01-31", "FREQ=MONTHLY"]),
+            Some(4),
+        )
+        .unwrap();
+        assert_eq!(row.todo.title, "Pay rent");
+        assert!(!row.completed);
+        assert_eq!(row.todo.list_id, Some(4));
+        assert_eq!(
+            row.todo.due_at.unwrap().to_rfc3339(),
+            "2027-01-31T00:00:00+00:00"
+        );
+        assert!(row.recurrence.is_some());
+
+        // Completed rows drop their rule
+        let row = parse_row(
+            &columns,
+            &headers(&["Paid", "yes", "", "", "FREQ=MONTHLY"]),
+            None,
+        )
+        .unwrap();
+        assert!(row.completed && row.recurrence.is_none());
+
+        assert!(parse_row(&columns, &headers(&["Short row"]), None).is_err());
+        assert!(parse_row(&columns, &headers(&["x", "maybe", "", "", ""]), None).is_err());
+    }
+
+    #[test]
+    fn formula_escapes_from_the_export_are_removed() {
+        let columns = map_columns(&headers(&["title", "description"]), &[]).unwrap();
+
+        let row = parse_row(&columns, &headers(&["'=1+1", "'-5 degrees"]), None).unwrap();
+        assert_eq!(row.todo.title, "=1+1");
+        assert_eq!(row.todo.description.as_deref(), Some("-5 degrees"));
+
+        // Apostrophes that are not escapes are kept
+        let row = parse_row(&columns, &headers(&["'til then", "'"]), None).unwrap();
+        assert_eq!(row.todo.title, "'til then");
+        assert_eq!(row.todo.description.as_deref(), Some("'"));
+    }
+}
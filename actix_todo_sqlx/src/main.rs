@@ -1,23 +1,91 @@
-use actix_web::{App, HttpServer, middleware::Logger, web};
-use utoipa::OpenApi;
+use actix_web::dev::Handler;
+use actix_web::http::Method;
+use actix_web::middleware::{ErrorHandlers, Logger};
+use actix_web::{App, FromRequest, HttpServer, Resource, Responder, guard, web};
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
 use utoipa_swagger_ui::SwaggerUi;
 
+mod auth;
+mod bulk;
+mod cache;
+mod config;
 mod database;
 mod errors;
+mod export;
 mod handlers;
+mod import;
 mod models;
+mod notifier;
+mod pagination;
+mod recurrence;
+mod retention;
+mod scheduler;
+mod search;
+mod webhooks;
 
+use actix_web::middleware::from_fn;
+use cache::ListCache;
+use config::{Command, OpenApiFormat};
 use database::create_pool;
 use handlers::*;
+use std::sync::Arc;
 
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        handlers::health_live,
+        handlers::health_ready,
+        handlers::register_handler,
+        handlers::login_handler,
         handlers::list_todos_handler,
         handlers::create_todo_handler,
+        handlers::search_todos_handler,
+        handlers::export_todos_handler,
+        handlers::import_todos_handler,
+        handlers::bulk_todos_handler,
+        handlers::todo_stats_handler,
+        handlers::overdue_todos_handler,
+        handlers::upcoming_todos_handler,
         handlers::get_todo_handler,
         handlers::update_todo_handler,
+        handlers::put_todo_handler,
         handlers::delete_todo_handler,
+        handlers::archive_todo_handler,
+        handlers::unarchive_todo_handler,
+        handlers::move_todo_handler,
+        handlers::todo_history_handler,
+        handlers::activity_handler,
+        handlers::list_dependencies_handler,
+        handlers::add_dependency_handler,
+        handlers::remove_dependency_handler,
+        handlers::list_todo_labels_handler,
+        handlers::add_todo_label_handler,
+        handlers::remove_todo_label_handler,
+        handlers::list_lists_handler,
+        handlers::create_list_handler,
+        handlers::get_list_handler,
+        handlers::update_list_handler,
+        handlers::delete_list_handler,
+        handlers::restore_list_handler,
+        handlers::list_list_todos_handler,
+        handlers::create_list_todo_handler,
+        handlers::reorder_list_handler,
+        handlers::list_members_handler,
+        handlers::add_list_member_handler,
+        handlers::remove_list_member_handler,
+        handlers::list_labels_handler,
+        handlers::create_label_handler,
+        handlers::get_label_handler,
+        handlers::update_label_handler,
+        handlers::delete_label_handler,
+        handlers::list_webhooks_handler,
+        handlers::create_webhook_handler,
+        handlers::get_webhook_handler,
+        handlers::update_webhook_handler,
+        handlers::delete_webhook_handler,
+        handlers::list_deliveries_handler,
+        handlers::retry_delivery_handler,
     ),
     components(
         schemas(
@@ -25,53 +93,474 @@ use handlers::*;
             models::CreateTodo,
             models::UpdateTodo,
             models::Priority,
+            models::TodoTree,
+            models::SearchHit,
+            models::SearchPage,
+            models::ExportFormat,
+            models::ImportReport,
+            models::ImportRowError,
+            models::Webhook,
+            models::CreateWebhook,
+            models::UpdateWebhook,
+            models::DeliveryStatus,
+            models::WebhookDelivery,
+            models::DeliveryPage,
+            models::TodoAction,
+            models::TodoEvent,
+            models::EventPage,
+            models::AddDependency,
+            models::Label,
+            models::CreateLabel,
+            models::UpdateLabel,
+            models::AddTodoLabel,
+            models::LabelMatch,
+            models::LabelStats,
+            models::BulkMode,
+            models::BulkOperation,
+            models::BulkFilter,
+            models::BulkAction,
+            models::BulkRequest,
+            models::BulkItemResult,
+            models::BulkResponse,
+            models::SortField,
+            models::SortOrder,
+            models::TodoPage,
+            models::PageLinks,
             models::TodoStats,
             models::PriorityStats,
+            models::StatsGrouping,
+            models::TrendPoint,
+            models::AuthRequest,
+            models::AuthResponse,
+            models::List,
+            models::CreateList,
+            models::UpdateList,
+            models::ListDeleteMode,
+            models::ReorderTodos,
+            models::MoveTodo,
+            models::ListRole,
+            models::ListMember,
+            models::AddListMember,
+            models::HealthResponse,
+            models::PoolStats,
+            models::CacheStats,
             errors::ErrorResponse,
+            errors::FieldError,
         )
     ),
+    modifiers(&BearerAuth),
+    security(("bearer_auth" = [])),
     tags(
+        (name = "auth", description = "Registration and login"),
         (name = "todos", description = "Todo management API"),
+        (name = "lists", description = "Shared lists and their members"),
+        (name = "labels", description = "Personal, color-coded labels for todos"),
+        (name = "webhooks", description = "Outbound notifications of todo events"),
         (name = "health", description = "Health check endpoints")
     ),
     info(
         title = "Todo API",
         version = "1.0.0",
         description = "A simple Todo management API built with Actix Web and PostgreSQL"
-    ),
-    servers(
-        (url = "/api/v1/", description="Local Server")
     )
 )]
 struct ApiDoc;
 
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "bearer_auth",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .bearer_format("JWT")
+                        .build(),
+                ),
+            );
+        }
+    }
+}
+
+// One registered operation. Routes are kept as data so the tests can check
+// each of them against the OpenAPI document.
+struct ApiRoute {
+    #[cfg_attr(not(test), allow(dead_code))]
+    method: Method,
+    #[cfg_attr(not(test), allow(dead_code))]
+    path: &'static str,
+    resource: Resource,
+}
+
+// Registers like App::route: the method guard sits on the resource, so
+// another method on the same path falls through to the next resource
+fn route<F, Args>(method: Method, path: &'static str, handler: F) -> ApiRoute
+where
+    F: Handler<Args>,
+    Args: FromRequest + 'static,
+    F::Output: Responder + 'static,
+{
+    ApiRoute {
+        resource: web::resource(path)
+            .guard(guard::Method(method.clone()))
+            .to(handler),
+        method,
+        path,
+    }
+}
+
+impl ApiRoute {
+    fn app_data<U: 'static>(mut self, data: U) -> Self {
+        self.resource = self.resource.app_data(data);
+        self
+    }
+}
+
+fn health_routes() -> Vec<ApiRoute> {
+    vec![
+        route(Method::GET, "/health/live", health_live),
+        route(Method::GET, "/health/ready", health_ready),
+    ]
+}
+
+// Mounted under /api/v1; more specific paths come before /todos/{id}
+fn api_routes() -> Vec<ApiRoute> {
+    vec![
+        route(Method::POST, "/auth/register", register_handler),
+        route(Method::POST, "/auth/login", login_handler),
+        route(Method::GET, "/todos", list_todos_handler),
+        route(Method::POST, "/todos", create_todo_handler),
+        route(Method::GET, "/todos/search", search_todos_handler),
+        route(Method::GET, "/todos/export", export_todos_handler),
+        route(Method::POST, "/todos/import", import_todos_handler)
+            .app_data(web::PayloadConfig::new(import::MAX_IMPORT_BYTES)),
+        route(Method::POST, "/todos/bulk", bulk_todos_handler),
+        route(Method::GET, "/todos/stats", todo_stats_handler),
+        route(Method::GET, "/todos/overdue", overdue_todos_handler),
+        route(Method::GET, "/todos/upcoming", upcoming_todos_handler),
+        route(Method::GET, "/todos/{id}", get_todo_handler),
+        route(Method::PATCH, "/todos/{id}", update_todo_handler),
+        // PUT predates PATCH and is kept as an alias
+        route(Method::PUT, "/todos/{id}", put_todo_handler),
+        route(Method::DELETE, "/todos/{id}", delete_todo_handler),
+        route(Method::POST, "/todos/{id}/archive", archive_todo_handler),
+        route(
+            Method::POST,
+            "/todos/{id}/unarchive",
+            unarchive_todo_handler,
+        ),
+        route(Method::POST, "/todos/{id}/move", move_todo_handler),
+        route(Method::GET, "/todos/{id}/history", todo_history_handler),
+        route(
+            Method::GET,
+            "/todos/{id}/dependencies",
+            list_dependencies_handler,
+        ),
+        route(
+            Method::POST,
+            "/todos/{id}/dependencies",
+            add_dependency_handler,
+        ),
+        route(
+            Method::DELETE,
+            "/todos/{id}/dependencies/{depends_on_id}",
+            remove_dependency_handler,
+        ),
+        route(Method::GET, "/todos/{id}/labels", list_todo_labels_handler),
+        route(Method::POST, "/todos/{id}/labels", add_todo_label_handler),
+        route(
+            Method::DELETE,
+            "/todos/{id}/labels/{label_id}",
+            remove_todo_label_handler,
+        ),
+        route(Method::GET, "/activity", activity_handler),
+        route(Method::GET, "/lists", list_lists_handler),
+        route(Method::POST, "/lists", create_list_handler),
+        route(Method::GET, "/lists/{id}", get_list_handler),
+        route(Method::PUT, "/lists/{id}", update_list_handler),
+        route(Method::DELETE, "/lists/{id}", delete_list_handler),
+        route(Method::POST, "/lists/{id}/restore", restore_list_handler),
+        route(Method::GET, "/lists/{id}/todos", list_list_todos_handler),
+        route(Method::POST, "/lists/{id}/todos", create_list_todo_handler),
+        route(Method::PUT, "/lists/{id}/todos/order", reorder_list_handler),
+        route(Method::GET, "/lists/{id}/members", list_members_handler),
+        route(Method::POST, "/lists/{id}/members", add_list_member_handler),
+        route(
+            Method::DELETE,
+            "/lists/{id}/members/{user_id}",
+            remove_list_member_handler,
+        ),
+        route(Method::GET, "/labels", list_labels_handler),
+        route(Method::POST, "/labels", create_label_handler),
+        route(Method::GET, "/labels/{id}", get_label_handler),
+        route(Method::PATCH, "/labels/{id}", update_label_handler),
+        route(Method::DELETE, "/labels/{id}", delete_label_handler),
+        route(Method::GET, "/webhooks", list_webhooks_handler),
+        route(Method::POST, "/webhooks", create_webhook_handler),
+        route(Method::GET, "/webhooks/{id}", get_webhook_handler),
+        route(Method::PATCH, "/webhooks/{id}", update_webhook_handler),
+        route(Method::DELETE, "/webhooks/{id}", delete_webhook_handler),
+        route(
+            Method::GET,
+            "/webhooks/{id}/deliveries",
+            list_deliveries_handler,
+        ),
+        route(
+            Method::POST,
+            "/webhooks/{id}/deliveries/{delivery_id}/retry",
+            retry_delivery_handler,
+        ),
+    ]
+}
+
+fn routes(cfg: &mut web::ServiceConfig) {
+    for route in health_routes() {
+        cfg.service(route.resource);
+    }
+
+    let mut api = web::scope("/api/v1").wrap(from_fn(cache::invalidate_on_write));
+    for route in api_routes() {
+        api = api.service(route.resource);
+    }
+    cfg.service(api);
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    env_logger::init();
+    let settings = match config::load() {
+        Ok(Command::Serve(settings)) => settings,
+        Ok(Command::PrintOpenApi(format)) => {
+            let openapi = ApiDoc::openapi();
+            let document = match format {
+                OpenApiFormat::Json => openapi.to_pretty_json().map_err(std::io::Error::other)?,
+                OpenApiFormat::Yaml => openapi.to_yaml().map_err(std::io::Error::other)?,
+            };
+            println!("{}", document);
+            return Ok(());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    env_logger::Builder::new()
+        .parse_filters(&settings.log_level)
+        .init();
 
-    let pool = create_pool()
+    println!("Loaded settings: {:#?}", settings); // secrets print as [REDACTED]
+
+    let pool = create_pool(&settings.database)
         .await
-        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?; //pool owned by main
+        .map_err(std::io::Error::other)?; //pool owned by main
+
+    if settings.repair_priorities {
+        let repairs = database::repair_priorities(&pool)
+            .await
+            .map_err(std::io::Error::other)?;
+        for repair in &repairs {
+            println!(
+                "todo {}: priority {:?} -> {:?}",
+                repair.id, repair.old_priority, repair.new_priority
+            );
+        }
+        println!("Repaired {} todo(s)", repairs.len());
+        return Ok(());
+    }
+
+    if settings.reminders.enabled {
+        scheduler::spawn_reminder_scheduler(
+            pool.clone(),
+            settings.reminders.clone(),
+            notifier::build_notifier(&settings.reminders.notifier),
+        );
+    }
+
+    if settings.webhooks.enabled {
+        webhooks::spawn_webhook_worker(pool.clone(), settings.webhooks.clone());
+    }
+
+    let cache = Arc::new(ListCache::new(settings.cache.clone()));
 
-    HttpServer::new(move || {
+    if settings.retention.enabled {
+        retention::spawn_retention_job(pool.clone(), settings.retention.clone(), cache.clone());
+    }
+
+    let auth_settings = settings.auth.clone();
+    let webhook_settings = settings.webhooks.clone();
+
+    let mut server = HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(pool.clone())) // //pool ownership move into the closure
+            .app_data(web::Data::new(auth_settings.clone()))
+            .app_data(web::Data::new(webhook_settings.clone()))
+            .app_data(web::Data::from(cache.clone()))
+            // Every error is answered with problem details, including the
+            // ones the extractors and the router produce
+            .app_data(web::JsonConfig::default().error_handler(errors::json_error_handler))
+            .app_data(web::PathConfig::default().error_handler(errors::path_error_handler))
+            .app_data(web::QueryConfig::default().error_handler(errors::query_error_handler))
+            .wrap(ErrorHandlers::new().default_handler(errors::problem_fallback))
             .wrap(Logger::default())
-            .route("/health", web::get().to(health_check))
-            .service(
-                web::scope("/api/v1")
-                    .route("/todos", web::get().to(list_todos_handler))
-                    .route("/todos", web::post().to(create_todo_handler))
-                    .route("/todos/{id}", web::get().to(get_todo_handler))
-                    .route("/todos/{id}", web::put().to(update_todo_handler))
-                    .route("/todos/{id}", web::delete().to(delete_todo_handler)),
-            )
+            .configure(routes)
             .service(
                 SwaggerUi::new("/swagger-ui/{_:.*}")
                     .url("/api-docs/openapi.json", ApiDoc::openapi()),
             )
-    })
-    .bind("127.0.0.1:8080")?
-    .run()
-    .await
+    });
+
+    if let Some(workers) = settings.workers {
+        server = server.workers(workers);
+    }
+
+    server.bind(settings.bind_address)?.run().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::HttpResponse;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{TestRequest, call_service, init_service};
+    use serde_json::Value;
+    use utoipa::openapi::PathItemType;
+
+    // Stands in for the fallback 404, so that a handler answering 404 is not
+    // mistaken for a missing route
+    async fn unmatched() -> HttpResponse {
+        HttpResponse::ImATeapot().finish()
+    }
+
+    fn method(kind: &PathItemType) -> Method {
+        match kind {
+            PathItemType::Get => Method::GET,
+            PathItemType::Post => Method::POST,
+            PathItemType::Put => Method::PUT,
+            PathItemType::Delete => Method::DELETE,
+            PathItemType::Options => Method::OPTIONS,
+            PathItemType::Head => Method::HEAD,
+            PathItemType::Patch => Method::PATCH,
+            PathItemType::Trace => Method::TRACE,
+            PathItemType::Connect => Method::CONNECT,
+        }
+    }
+
+    // "/todos/{id}" -> "/todos/1"; any segment value matches the route
+    fn concrete(path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with('{') {
+                    "1"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[actix_web::test]
+    async fn documented_operations_are_routed() {
+        let app = init_service(
+            App::new()
+                .configure(routes)
+                .default_service(web::to(unmatched)),
+        )
+        .await;
+
+        let openapi = ApiDoc::openapi();
+        assert!(!openapi.paths.paths.is_empty());
+
+        for (path, item) in &openapi.paths.paths {
+            for kind in item.operations.keys() {
+                let method = method(kind);
+                let req = TestRequest::default()
+                    .method(method.clone())
+                    .uri(&concrete(path))
+                    .to_request();
+                let res = call_service(&app, req).await;
+
+                assert_ne!(
+                    res.status(),
+                    StatusCode::IM_A_TEAPOT,
+                    "{} {} is documented but has no route",
+                    method,
+                    path
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn registered_routes_are_documented() {
+        let openapi = ApiDoc::openapi();
+        let documented: Vec<(Method, &str)> = openapi
+            .paths
+            .paths
+            .iter()
+            .flat_map(|(path, item)| {
+                item.operations
+                    .keys()
+                    .map(move |kind| (method(kind), path.as_str()))
+            })
+            .collect();
+
+        let registered = health_routes()
+            .into_iter()
+            .map(|route| (route.method, route.path.to_string()))
+            .chain(
+                api_routes()
+                    .into_iter()
+                    .map(|route| (route.method, format!("/api/v1{}", route.path))),
+            );
+
+        for (method, path) in registered {
+            assert!(
+                documented.contains(&(method.clone(), path.as_str())),
+                "{} {} is routed but not documented",
+                method,
+                path
+            );
+        }
+    }
+
+    fn collect_refs<'a>(value: &'a Value, refs: &mut Vec<&'a str>) {
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match (key.as_str(), value) {
+                        ("$ref", Value::String(reference)) => refs.push(reference),
+                        _ => collect_refs(value, refs),
+                    }
+                }
+            }
+            Value::Array(values) => values.iter().for_each(|value| collect_refs(value, refs)),
+            _ => {}
+        }
+    }
+
+    // Response bodies refer to schemas by name only, so a schema missing from
+    // `components` still compiles
+    #[test]
+    fn schema_references_resolve() {
+        let document = serde_json::to_value(ApiDoc::openapi()).unwrap();
+        let schemas = &document["components"]["schemas"];
+
+        let mut refs = Vec::new();
+        collect_refs(&document, &mut refs);
+        assert!(!refs.is_empty());
+
+        for reference in refs {
+            let name = reference
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected reference {}", reference));
+            assert!(
+                schemas.get(name).is_some(),
+                "{} is referenced but not registered as a schema",
+                name
+            );
+        }
+    }
 }
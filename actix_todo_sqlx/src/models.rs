@@ -1,23 +1,37 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 use sqlx::{FromRow, Type};
-use utoipa::ToSchema;
+use std::collections::HashMap;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::errors::{AppError, ErrorResponse, FieldError};
 
 #[derive(Debug, Serialize, Deserialize, FromRow, ToSchema)] // FromRow enables query_as
 pub struct Todo {
-    pub id: Option<i32>,
+    pub id: i32,
     pub title: String,
     pub description: Option<String>,
     pub completed: bool,
     pub priority: Priority, //SQLx knows how to convert
+    pub due_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>, // set when completed flips to true
+    /// RRULE subset, e.g. FREQ=WEEKLY;BYDAY=MO,TH
+    pub recurrence: Option<String>,
+    pub parent_id: Option<i32>, // set for subtasks
+    pub owner_id: Option<Uuid>, // the user who created it
+    pub list_id: Option<i32>,   // shared list the todo belongs to, if any
+    pub position: Option<f64>,  // manual order within the list, or among the owner's own todos
+    /// Set while the todo is archived; archived todos are left out of listings
+    pub archived_at: Option<DateTime<Utc>>,
     pub created_at: Option<DateTime<Utc>>,
     pub updated_at: Option<DateTime<Utc>>,
 }
 
+// Stored as TEXT; the todos_priority_check constraint keeps the column to these values
 #[derive(Debug, Serialize, Deserialize, Clone, Type, ToSchema)]
 #[serde(rename_all = "lowercase")] // For JSON (HTTP requests/responses)
 #[sqlx(type_name = "text", rename_all = "lowercase")] // For database conversion
-
 pub enum Priority {
     Low,
     Medium,
@@ -26,25 +40,827 @@ pub enum Priority {
 }
 
 //implementing default trait of priority enum
+#[allow(clippy::derivable_impls)]
 impl Default for Priority {
     fn default() -> Self {
         Priority::Medium
     }
 }
 
+impl Priority {
+    // Ordinal used for sorting (and keyset cursors) in urgency order
+    pub fn rank(&self) -> i32 {
+        match self {
+            Priority::Low => 0,
+            Priority::Medium => 1,
+            Priority::High => 2,
+            Priority::Urgent => 3,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Low => "low",
+            Priority::Medium => "medium",
+            Priority::High => "high",
+            Priority::Urgent => "urgent",
+        }
+    }
+
+    // Case-insensitive, for input that does not go through serde
+    pub fn from_name(name: &str) -> Option<Priority> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "low" => Some(Priority::Low),
+            "medium" => Some(Priority::Medium),
+            "high" => Some(Priority::High),
+            "urgent" => Some(Priority::Urgent),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Deserialize, ToSchema)]
 pub struct CreateTodo {
     pub title: String,
     pub description: Option<String>,
     pub priority: Option<Priority>,
+    pub due_at: Option<DateTime<Utc>>,
+    /// FREQ=DAILY|WEEKLY|MONTHLY with optional INTERVAL, BYDAY (weekly) or BYMONTHDAY (monthly)
+    pub recurrence: Option<String>,
+    /// Makes this todo a subtask of the given todo
+    pub parent_id: Option<i32>,
+    /// Shared list to add the todo to (requires the editor or owner role).
+    /// Subtasks default to their parent's list.
+    pub list_id: Option<i32>,
 }
 
-#[derive(Debug, Deserialize, ToSchema)]
+pub const MAX_TITLE_LENGTH: usize = 200;
+
+fn check_title(title: &str, errors: &mut Vec<FieldError>) {
+    if title.trim().is_empty() {
+        errors.push(FieldError::new("title", "must not be empty"));
+    } else if title.chars().count() > MAX_TITLE_LENGTH {
+        errors.push(FieldError::new(
+            "title",
+            format!("must be at most {} characters", MAX_TITLE_LENGTH),
+        ));
+    }
+}
+
+fn validated(errors: Vec<FieldError>) -> Result<(), AppError> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::Validation(errors))
+    }
+}
+
+impl CreateTodo {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        check_title(&self.title, &mut errors);
+        validated(errors)
+    }
+}
+
+// Tells a missing field (None) apart from an explicit null (Some(None));
+// used with #[serde(default)]
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// JSON Merge Patch (RFC 7396): absent fields are left alone and null clears
+// the nullable ones. title, completed and priority cannot be cleared, so a
+// null there is kept as Some(None) and rejected by validate.
+#[derive(Debug, Default, Deserialize, ToSchema)]
 pub struct UpdateTodo {
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub title: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<bool>)]
+    pub completed: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<Priority>)]
+    pub priority: Option<Option<Priority>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<DateTime<Utc>>)]
+    pub due_at: Option<Option<DateTime<Utc>>>,
+    /// Replaces (or with null, removes) the recurrence rule. Completing a recurring todo creates the next occurrence.
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub recurrence: Option<Option<String>>,
+    /// Moves this todo under another todo (null makes it top-level); a todo cannot become its own descendant
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub parent_id: Option<Option<i32>>,
+    /// Moves this todo and its subtasks to the end of another list (null takes them out of their list)
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub list_id: Option<Option<i32>>,
+}
+
+impl UpdateTodo {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        match &self.title {
+            Some(Some(title)) => check_title(title, &mut errors),
+            Some(None) => errors.push(FieldError::new("title", "must not be null")),
+            None => {}
+        }
+        if self.completed == Some(None) {
+            errors.push(FieldError::new("completed", "must not be null"));
+        }
+        if matches!(self.priority, Some(None)) {
+            errors.push(FieldError::new("priority", "must not be null"));
+        }
+        validated(errors)
+    }
+
+    // The new recurrence rule, if one is being set
+    pub fn recurrence_rule(&self) -> Option<&str> {
+        self.recurrence.as_ref().and_then(Option::as_deref)
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TodoQuery {
+    /// Include all subtasks (recursively) with rolled-up completion
+    pub subtree: Option<bool>,
+}
+
+// A todo with its subtasks; completion_percent is the share of completed
+// descendants (or 0/100 for a todo without subtasks)
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TodoTree {
+    #[serde(flatten)]
+    pub todo: Todo,
+    pub completion_percent: f64,
+    pub subtasks: Vec<TodoTree>,
+}
+
+impl TodoTree {
+    // Builds the tree rooted at `root` from the root and all of its descendants
+    pub fn build(root: Todo, descendants: Vec<Todo>) -> Self {
+        let mut children: HashMap<i32, Vec<Todo>> = HashMap::new();
+        for todo in descendants {
+            if let Some(parent_id) = todo.parent_id {
+                children.entry(parent_id).or_default().push(todo);
+            }
+        }
+
+        Self::build_node(root, &mut children).0
+    }
+
+    // Returns the node plus (descendants, completed descendants) for roll-up
+    fn build_node(todo: Todo, children: &mut HashMap<i32, Vec<Todo>>) -> (Self, usize, usize) {
+        let mut subtasks = Vec::new();
+        let mut total = 0;
+        let mut completed = 0;
+
+        for child in children.remove(&todo.id).unwrap_or_default() {
+            let child_completed = child.completed;
+            let (node, child_total, child_done) = Self::build_node(child, children);
+            total += 1 + child_total;
+            completed += usize::from(child_completed) + child_done;
+            subtasks.push(node);
+        }
+
+        let completion_percent = if total == 0 {
+            if todo.completed { 100.0 } else { 0.0 }
+        } else {
+            (completed as f64 * 1000.0 / total as f64).round() / 10.0
+        };
+
+        (
+            TodoTree {
+                todo,
+                completion_percent,
+                subtasks,
+            },
+            total,
+            completed,
+        )
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddDependency {
+    /// Todo that must be completed before this one can be
+    pub depends_on_id: i32,
+}
+
+pub const MAX_LABEL_NAME_LENGTH: usize = 50;
+
+// Labels are private to the user who created them: each user sees only their
+// own labels on a todo, also on todos in shared lists.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Label {
+    pub id: i32,
+    pub name: String,
+    /// Hex color such as #1e90ff
+    pub color: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateLabel {
+    pub name: String,
+    #[schema(example = "#1e90ff")]
+    pub color: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateLabel {
+    pub name: Option<String>,
+    pub color: Option<String>,
+}
+
+fn check_label_name(name: &str, errors: &mut Vec<FieldError>) {
+    if name.trim().is_empty() {
+        errors.push(FieldError::new("name", "must not be empty"));
+    } else if name.trim().chars().count() > MAX_LABEL_NAME_LENGTH {
+        errors.push(FieldError::new(
+            "name",
+            format!("must be at most {} characters", MAX_LABEL_NAME_LENGTH),
+        ));
+    }
+}
+
+fn check_color(color: &str, errors: &mut Vec<FieldError>) {
+    let hex = color.strip_prefix('#').unwrap_or("");
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        errors.push(FieldError::new(
+            "color",
+            "must be a hex color such as #1e90ff",
+        ));
+    }
+}
+
+impl CreateLabel {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        check_label_name(&self.name, &mut errors);
+        check_color(&self.color, &mut errors);
+        validated(errors)
+    }
+}
+
+impl UpdateLabel {
+    pub fn validate(&self) -> Result<(), AppError> {
+        let mut errors = Vec::new();
+        if let Some(name) = &self.name {
+            check_label_name(name, &mut errors);
+        }
+        if let Some(color) = &self.color {
+            check_color(color, &mut errors);
+        }
+        validated(errors)
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddTodoLabel {
+    pub label_id: i32,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelMatch {
+    /// Todos with at least one of the labels
+    #[default]
+    Any,
+    /// Todos with every one of the labels
+    All,
+}
+
+// "1,2,3" in query strings; serialized back the same way for page links
+fn comma_separated<'de, D>(deserializer: D) -> Result<Option<Vec<i32>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let Some(text) = Option::<String>::deserialize(deserializer)? else {
+        return Ok(None);
+    };
+
+    text.split(',')
+        .map(str::trim)
+        .filter(|id| !id.is_empty())
+        .map(|id| {
+            id.parse::<i32>()
+                .map_err(|_| de::Error::custom(format!("invalid label id '{}'", id)))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(|ids| (!ids.is_empty()).then_some(ids))
+}
+
+fn join_commas<S>(ids: &Option<Vec<i32>>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let text = ids
+        .iter()
+        .flatten()
+        .map(i32::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    serializer.serialize_str(&text)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    Id,
+    Title,
+    Description,
+    Completed,
+    Priority,
+    /// Todos without a due date come last in ascending order
+    DueAt,
+    #[default]
+    CreatedAt,
+    UpdatedAt,
+    /// Manual order within a list
+    Position,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// Query string for GET /todos. Serialize is used to rebuild the page links.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TodoListQuery {
+    /// Only completed (true) or pending (false) todos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Priority>,
+    /// Only direct subtasks of this todo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_id: Option<i32>,
+    /// Only todos in this shared list
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub list_id: Option<i32>,
+    /// Case-insensitive substring of the title
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
-    pub description: Option<String>,
+    /// Comma-separated ids of the caller's labels
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "comma_separated",
+        serialize_with = "join_commas"
+    )]
+    #[param(value_type = Option<String>, example = "1,2")]
+    pub labels: Option<Vec<i32>>,
+    /// Whether todos need any (default) or all of `labels`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label_match: Option<LabelMatch>,
+    /// Only archived (true) or unarchived (false, default) todos
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub created_before: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_after: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub updated_before: Option<DateTime<Utc>>,
+    /// Column to sort by (default: created_at)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<SortField>,
+    /// Sort direction (default: desc)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<SortOrder>,
+    /// Page size (default 50, max 200)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    /// Rows to skip; cannot be combined with cursor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<i64>,
+    /// Opaque keyset cursor taken from a previous page's next_cursor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// Every operation succeeds or none are applied
+    #[default]
+    Atomic,
+    /// Failed operations are rolled back individually; the rest are kept
+    BestEffort,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum BulkOperation {
+    Create(CreateTodo),
+    Update { id: i32, changes: UpdateTodo },
+    Delete { id: i32 },
+}
+
+// The filters of GET /todos that make sense for selecting todos to change
+#[derive(Debug, Default, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BulkFilter {
+    pub completed: Option<bool>,
+    pub priority: Option<Priority>,
+    pub parent_id: Option<i32>,
+    pub list_id: Option<i32>,
+    pub title: Option<String>,
+    /// Ids of the caller's labels
+    pub labels: Option<Vec<i32>>,
+    pub label_match: Option<LabelMatch>,
+    pub archived: Option<bool>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub updated_after: Option<DateTime<Utc>>,
+    pub updated_before: Option<DateTime<Utc>>,
+}
+
+impl From<BulkFilter> for TodoListQuery {
+    fn from(filter: BulkFilter) -> Self {
+        TodoListQuery {
+            completed: filter.completed,
+            priority: filter.priority,
+            parent_id: filter.parent_id,
+            list_id: filter.list_id,
+            title: filter.title,
+            labels: filter.labels.filter(|labels| !labels.is_empty()),
+            label_match: filter.label_match,
+            archived: filter.archived,
+            created_after: filter.created_after,
+            created_before: filter.created_before,
+            updated_after: filter.updated_after,
+            updated_before: filter.updated_before,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkAction {
+    Update,
+    Delete,
+}
+
+// Either a list of operations, or a filter plus an action applied to every
+// matching todo (e.g. filter {"priority": "high"}, action "update",
+// changes {"completed": true})
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(deny_unknown_fields)]
+pub struct BulkRequest {
+    #[serde(default)]
+    pub mode: BulkMode,
+    pub operations: Option<Vec<BulkOperation>>,
+    pub filter: Option<BulkFilter>,
+    pub action: Option<BulkAction>,
+    /// Fields to change when action is "update"
+    pub changes: Option<UpdateTodo>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkItemResult {
+    /// Position in `operations`, or in the list of todos matched by `filter`
+    pub index: usize,
+    pub op: String,
+    pub id: Option<i32>,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub todo: Option<Todo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ErrorResponse>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkResponse {
+    pub mode: BulkMode,
+    /// False when an atomic request was rolled back
+    pub committed: bool,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<BulkItemResult>,
+}
+
+// A subscription to todo events. `secret` signs the deliveries and is only
+// returned when the webhook is created or its secret is rotated.
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    /// Any of todo.created, todo.updated, todo.completed and todo.deleted
+    pub events: Vec<String>,
+    /// Only events for todos in this list
+    pub list_id: Option<i32>,
+    /// Paused webhooks queue no new deliveries and send none
+    pub active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[sqlx(default)]
+    pub secret: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateWebhook {
+    /// http or https URL that receives a POST per event
+    pub url: String,
+    pub events: Vec<String>,
+    pub list_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateWebhook {
+    pub url: Option<String>,
+    pub events: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<i32>)]
+    pub list_id: Option<Option<i32>>,
+    pub active: Option<bool>,
+    /// Replace the signing secret; the new one is returned in the response
+    pub rotate_secret: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// Waiting for its first attempt or for a retry
+    Pending,
+    Delivered,
+    /// Gave up after the maximum number of attempts
+    Dead,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub webhook_id: i32,
+    pub event: String,
+    /// The JSON body that is (or was) sent
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    /// When a pending delivery is next tried
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    /// HTTP status of the last attempt, if the receiver answered
+    pub response_status: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveriesQuery {
+    pub status: Option<DeliveryStatus>,
+    /// Page size (default 50, max 200)
+    pub limit: Option<i64>,
+    /// Only deliveries older than this id; pass the previous page's next_before
+    pub before: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeliveryPage {
+    /// Newest first
+    pub data: Vec<WebhookDelivery>,
+    pub limit: i64,
+    /// Set when there are older deliveries
+    pub next_before: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One row per todo, with a header row
+    Csv,
+    /// iCalendar; only todos with a due date, as VTODO entries
+    Ics,
+    /// A JSON array of todos
+    Json,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportQuery {
+    pub format: ExportFormat,
+    pub completed: Option<bool>,
+    pub priority: Option<Priority>,
+    /// Only todos in this shared list
+    pub list_id: Option<i32>,
+    /// Export archived todos instead of the others
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportQuery {
+    /// Validate every row and report what would happen, without saving anything
+    pub dry_run: Option<bool>,
+    /// Maps CSV headers to fields, as `Header:field` pairs separated by commas
+    /// (e.g. `Task:title,Notes:description,Due:due_at`)
+    pub map: Option<String>,
+    /// List for rows that do not set list_id
+    pub list_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportRowError {
+    /// Row number in the file; the header is row 1
+    pub row: usize,
+    pub error: ErrorResponse,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// Data rows in the file
+    pub rows: usize,
+    /// Rows saved (or, in a dry run, rows that would be saved)
+    pub imported: usize,
+    pub failed: usize,
+    /// Headers that are not mapped to any field
+    pub ignored_columns: Vec<String>,
+    pub errors: Vec<ImportRowError>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct UpcomingQuery {
+    /// Look-ahead window such as 90m, 24h, 7d or 2w (default 24h)
+    pub within: Option<String>,
+}
+
+// Emitted by the reminder scheduler for a todo approaching its due time
+#[derive(Debug, Serialize)]
+pub struct ReminderEvent {
+    pub event: &'static str,
+    pub todo_id: i32,
+    pub owner_id: Option<Uuid>,
+    pub title: String,
+    pub priority: Priority,
+    pub due_at: DateTime<Utc>,
+}
+
+impl ReminderEvent {
+    pub fn due_soon(todo: &Todo) -> Option<Self> {
+        Some(ReminderEvent {
+            event: "todo.due_soon",
+            todo_id: todo.id,
+            owner_id: todo.owner_id,
+            title: todo.title.clone(),
+            priority: todo.priority.clone(),
+            due_at: todo.due_at?,
+        })
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PageLinks {
+    #[serde(rename = "self")]
+    pub self_link: String,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TodoPage {
+    pub data: Vec<Todo>,
+    /// Number of todos matching the filters (ignores limit/offset/cursor)
+    pub total: i64,
+    pub limit: i64,
+    pub offset: Option<i64>,
+    pub next_cursor: Option<String>,
+    pub links: PageLinks,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SearchQuery {
+    /// Words, "quoted phrases", prefix* and -excluded terms, all of which must match
+    pub q: String,
     pub completed: Option<bool>,
     pub priority: Option<Priority>,
+    pub list_id: Option<i32>,
+    /// Page size (default 50, max 200)
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    #[sqlx(flatten)]
+    pub todo: Todo,
+    /// Relevance; title matches weigh more than description matches
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SearchPage {
+    /// Best matches first
+    pub data: Vec<SearchHit>,
+    pub total: i64,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum TodoAction {
+    Created,
+    Updated,
+    Deleted,
+}
+
+// One row of the audit trail written by the todos_audit trigger
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct TodoEvent {
+    pub id: i64,
+    pub todo_id: i32,
+    pub list_id: Option<i32>,
+    pub action: TodoAction,
+    /// None for changes made outside the API, such as --repair-priorities or the retention job
+    pub actor_id: Option<Uuid>,
+    pub actor_email: Option<String>,
+    /// Changed fields as {"field": {"before": ..., "after": ...}}
+    #[schema(value_type = Object)]
+    pub changes: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct HistoryQuery {
+    /// Page size (default 50, max 200)
+    pub limit: Option<i64>,
+    /// Only events older than this event id; pass the previous page's next_before
+    pub before: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ActivityQuery {
+    /// Only events for todos in this list
+    pub list_id: Option<i32>,
+    /// Page size (default 50, max 200)
+    pub limit: Option<i64>,
+    /// Only events older than this event id; pass the previous page's next_before
+    pub before: Option<i64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EventPage {
+    /// Newest first
+    pub data: Vec<TodoEvent>,
+    pub limit: i64,
+    /// Set when there are older events
+    pub next_before: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsGrouping {
+    Day,
+    Week,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct StatsQuery {
+    /// Bucket todos by creation day or (ISO) week to show completion trends
+    pub group_by: Option<StatsGrouping>,
+    /// Only count todos created at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only count todos created before this time
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct TrendPoint {
+    pub period_start: DateTime<Utc>,
+    pub created: i64,
+    pub completed: i64,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -53,6 +869,10 @@ pub struct TodoStats {
     pub completed: i64,
     pub pending: i64,
     pub by_priority: PriorityStats,
+    /// Every label of the caller, with the todos carrying it
+    pub by_label: Vec<LabelStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trend: Option<Vec<TrendPoint>>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -62,3 +882,214 @@ pub struct PriorityStats {
     pub high: i64,
     pub urgent: i64,
 }
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct LabelStats {
+    pub id: i32,
+    pub name: String,
+    pub color: String,
+    pub total: i64,
+    pub completed: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+// Counters of the todo list cache since startup
+#[derive(Debug, Serialize, ToSchema)]
+pub struct CacheStats {
+    pub enabled: bool,
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct HealthResponse {
+    pub status: String,
+    pub service: String,
+    pub version: String,
+    pub database: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool: Option<PoolStats>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache: Option<CacheStats>,
+}
+
+#[derive(Debug, FromRow)]
+pub struct User {
+    pub id: Uuid,
+    pub email: String,
+    pub password_hash: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AuthRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AuthResponse {
+    /// Send as `Authorization: Bearer <token>`
+    pub token: String,
+    pub user_id: Uuid,
+}
+
+// JWT claims
+#[derive(Serialize, Deserialize)]
+pub struct Claims {
+    pub user_id: String,
+    pub exp: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "text", rename_all = "lowercase")]
+pub enum ListRole {
+    /// Manages members; can edit todos
+    Owner,
+    /// Creates, edits and deletes todos in the list
+    Editor,
+    /// Read-only access
+    Viewer,
+}
+
+impl ListRole {
+    pub fn can_edit(self) -> bool {
+        matches!(self, ListRole::Owner | ListRole::Editor)
+    }
+}
+
+// A shared list as seen by one member
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct List {
+    pub id: i32,
+    pub name: String,
+    /// The caller's role in this list
+    pub role: ListRole,
+    pub created_at: DateTime<Utc>,
+    /// Set when the list was deleted with mode=archive
+    pub archived_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateList {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateList {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListsQuery {
+    /// List archived lists instead of active ones
+    pub archived: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ListDeleteMode {
+    /// Hide the list and make it read-only; it can be restored later
+    #[default]
+    Archive,
+    /// Delete the list together with its todos
+    Cascade,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteListQuery {
+    /// archive (default) or cascade
+    pub mode: Option<ListDeleteMode>,
+}
+
+// Neighbors for POST /todos/{id}/move; at least one is required
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct MoveTodo {
+    /// Todo to place this one directly before
+    pub before_id: Option<i32>,
+    /// Todo to place this one directly after
+    pub after_id: Option<i32>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ReorderTodos {
+    /// Every todo in the list, in the new order
+    pub todo_ids: Vec<i32>,
+}
+
+#[derive(Debug, Serialize, FromRow, ToSchema)]
+pub struct ListMember {
+    pub user_id: Uuid,
+    pub email: String,
+    pub role: ListRole,
+    pub added_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct AddListMember {
+    /// Email of a registered user
+    pub email: String,
+    pub role: ListRole,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::ResponseError;
+    use actix_web::http::StatusCode;
+
+    fn patch(json: &str) -> UpdateTodo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn invalid_fields(changes: &UpdateTodo) -> Vec<String> {
+        match changes.validate() {
+            Err(AppError::Validation(errors)) => errors.into_iter().map(|e| e.field).collect(),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn null_is_rejected_for_fields_that_cannot_be_cleared() {
+        let changes = patch(r#"{"title": null, "completed": null, "priority": null}"#);
+        assert_eq!(
+            invalid_fields(&changes),
+            vec!["title", "completed", "priority"]
+        );
+        assert_eq!(
+            changes.validate().unwrap_err().status_code(),
+            StatusCode::UNPROCESSABLE_ENTITY
+        );
+    }
+
+    #[test]
+    fn null_clears_nullable_fields() {
+        let changes = patch(r#"{"description": null, "due_at": null, "list_id": null}"#);
+        assert!(changes.validate().is_ok());
+        assert_eq!(changes.description, Some(None));
+        assert_eq!(changes.due_at, Some(None));
+        assert_eq!(changes.list_id, Some(None));
+        assert_eq!(changes.title, None);
+        assert_eq!(changes.completed, None);
+    }
+
+    #[test]
+    fn present_values_are_validated() {
+        let changes = patch(r#"{"title": "Renamed", "completed": true, "priority": "high"}"#);
+        assert!(changes.validate().is_ok());
+        assert_eq!(changes.completed, Some(Some(true)));
+
+        assert_eq!(invalid_fields(&patch(r#"{"title": "  "}"#)), vec!["title"]);
+    }
+}
@@ -0,0 +1,72 @@
+use async_trait::async_trait;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::NotifierKind;
+use crate::models::ReminderEvent;
+
+// Destination for reminder events. New channels (email, chat, ...) only need
+// to implement this trait and be added to `build_notifier`.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &ReminderEvent) -> Result<(), String>;
+}
+
+pub struct LogNotifier;
+
+#[async_trait]
+impl Notifier for LogNotifier {
+    async fn notify(&self, event: &ReminderEvent) -> Result<(), String> {
+        log::info!(
+            "Reminder: todo {} \"{}\" ({:?}) is due at {}",
+            event.todo_id,
+            event.title,
+            event.priority,
+            event.due_at
+        );
+        Ok(())
+    }
+}
+
+// POSTs each event as JSON to a fixed URL
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("Failed to build HTTP client");
+
+        WebhookNotifier { client, url }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &ReminderEvent) -> Result<(), String> {
+        let response = self
+            .client
+            .post(&self.url)
+            .json(event)
+            .send()
+            .await
+            .map_err(|e| format!("Webhook request failed: {}", e))?;
+
+        if !response.status().is_success() {
+            return Err(format!("Webhook returned {}", response.status()));
+        }
+
+        Ok(())
+    }
+}
+
+pub fn build_notifier(kind: &NotifierKind) -> Arc<dyn Notifier> {
+    match kind {
+        NotifierKind::Log => Arc::new(LogNotifier),
+        NotifierKind::Webhook { url } => Arc::new(WebhookNotifier::new(url.clone())),
+    }
+}
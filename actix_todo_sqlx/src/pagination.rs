@@ -0,0 +1,242 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::errors::AppError;
+use crate::models::{PageLinks, SortField, Todo, TodoListQuery};
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 200;
+
+// Keyset cursor: the sort value and id of the last row on the previous page.
+// Sent to clients as base64url(JSON) so it stays opaque.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cursor {
+    pub sort: SortField,
+    pub value: serde_json::Value,
+    pub id: i32,
+}
+
+impl Cursor {
+    pub fn after(todo: &Todo, sort: SortField) -> Self {
+        let value = match sort {
+            SortField::Id => serde_json::json!(todo.id),
+            SortField::Title => serde_json::json!(todo.title),
+            SortField::Completed => serde_json::json!(todo.completed),
+            SortField::Priority => serde_json::json!(todo.priority.rank()),
+            // Missing values sort as database::sort_expression coalesces them
+            SortField::Description => {
+                serde_json::json!(todo.description.as_deref().unwrap_or_default())
+            }
+            SortField::DueAt => serde_json::json!(todo.due_at.unwrap_or(DateTime::<Utc>::MAX_UTC)),
+            SortField::CreatedAt => {
+                serde_json::json!(todo.created_at.unwrap_or(DateTime::UNIX_EPOCH))
+            }
+            SortField::UpdatedAt => {
+                serde_json::json!(todo.updated_at.unwrap_or(DateTime::UNIX_EPOCH))
+            }
+            SortField::Position => serde_json::json!(todo.position.unwrap_or_default()),
+        };
+
+        Cursor {
+            sort,
+            value,
+            id: todo.id,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        // Serializing a struct of plain values cannot fail
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).expect("cursor is serializable"))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, AppError> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .ok_or_else(|| AppError::BadRequest("Invalid cursor".to_string()))
+    }
+}
+
+pub fn page_size(limit: Option<i64>) -> Result<i64, AppError> {
+    match limit {
+        None => Ok(DEFAULT_PAGE_SIZE),
+        Some(limit) if (1..=MAX_PAGE_SIZE).contains(&limit) => Ok(limit),
+        Some(_) => Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        ))),
+    }
+}
+
+fn page_url(path: &str, query: &TodoListQuery) -> String {
+    match serde_urlencoded::to_string(query) {
+        Ok(qs) if !qs.is_empty() => format!("{}?{}", path, qs),
+        _ => path.to_string(),
+    }
+}
+
+// Links keep every filter/sort parameter and only move offset or cursor.
+// Keyset pages only link forward; offset pages link both ways.
+pub fn page_links(
+    path: &str,
+    query: &TodoListQuery,
+    limit: i64,
+    has_more: bool,
+    next_cursor: Option<&str>,
+) -> PageLinks {
+    let self_link = page_url(path, query);
+
+    if query.cursor.is_some() {
+        let next = next_cursor.map(|cursor| {
+            page_url(
+                path,
+                &TodoListQuery {
+                    cursor: Some(cursor.to_string()),
+                    ..query.clone()
+                },
+            )
+        });
+
+        return PageLinks {
+            self_link,
+            next,
+            prev: None,
+        };
+    }
+
+    let offset = query.offset.unwrap_or(0);
+    let with_offset = |offset: i64| {
+        page_url(
+            path,
+            &TodoListQuery {
+                offset: Some(offset),
+                limit: Some(limit),
+                ..query.clone()
+            },
+        )
+    };
+
+    PageLinks {
+        self_link,
+        next: has_more.then(|| with_offset(offset + limit)),
+        prev: (offset > 0).then(|| with_offset((offset - limit).max(0))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Priority;
+
+    fn todo(id: i32) -> Todo {
+        Todo {
+            id,
+            title: "Write tests".to_string(),
+            description: None,
+            completed: false,
+            priority: Priority::High,
+            due_at: None,
+            completed_at: None,
+            recurrence: None,
+            parent_id: None,
+            owner_id: None,
+            list_id: None,
+            position: None,
+            archived_at: None,
+            created_at: None,
+            updated_at: None,
+        }
+    }
+
+    fn round_trip(cursor: &Cursor) -> Cursor {
+        Cursor::decode(&cursor.encode()).unwrap()
+    }
+
+    #[test]
+    fn cursor_round_trips_for_every_sort_field() {
+        let mut todo = todo(42);
+        todo.created_at = Some(Utc::now());
+        todo.updated_at = Some(Utc::now());
+        todo.position = Some(1.5);
+        todo.description = Some("With a description".to_string());
+        todo.due_at = Some(Utc::now());
+
+        for sort in [
+            SortField::Id,
+            SortField::Title,
+            SortField::Description,
+            SortField::Completed,
+            SortField::Priority,
+            SortField::DueAt,
+            SortField::CreatedAt,
+            SortField::UpdatedAt,
+            SortField::Position,
+        ] {
+            let cursor = Cursor::after(&todo, sort);
+            let decoded = round_trip(&cursor);
+            assert_eq!(decoded.sort, sort);
+            assert_eq!(decoded.value, cursor.value);
+            assert_eq!(decoded.id, 42);
+        }
+    }
+
+    #[test]
+    fn encoded_cursor_is_url_safe() {
+        let mut todo = todo(7);
+        todo.title = "??>>~~ needs padding".to_string();
+        let encoded = Cursor::after(&todo, SortField::Title).encode();
+        assert!(
+            encoded
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "{}",
+            encoded
+        );
+    }
+
+    #[test]
+    fn missing_timestamps_use_the_epoch() {
+        let todo = todo(3);
+        for sort in [SortField::CreatedAt, SortField::UpdatedAt] {
+            let cursor = round_trip(&Cursor::after(&todo, sort));
+            let value: DateTime<Utc> = serde_json::from_value(cursor.value).unwrap();
+            assert_eq!(value, DateTime::UNIX_EPOCH);
+        }
+    }
+
+    #[test]
+    fn missing_due_dates_and_descriptions_sort_last_and_empty() {
+        let todo = todo(3);
+
+        let cursor = round_trip(&Cursor::after(&todo, SortField::DueAt));
+        let value: DateTime<Utc> = serde_json::from_value(cursor.value).unwrap();
+        assert_eq!(value, DateTime::<Utc>::MAX_UTC);
+
+        let cursor = round_trip(&Cursor::after(&todo, SortField::Description));
+        assert_eq!(cursor.value, "");
+    }
+
+    #[test]
+    fn malformed_cursors_are_rejected() {
+        let not_json = URL_SAFE_NO_PAD.encode(b"not json");
+        let wrong_shape = URL_SAFE_NO_PAD.encode(br#"{"sort":"id"}"#);
+        for cursor in ["", "%%%", not_json.as_str(), wrong_shape.as_str()] {
+            assert!(
+                matches!(Cursor::decode(cursor), Err(AppError::BadRequest(_))),
+                "{:?}",
+                cursor
+            );
+        }
+    }
+
+    #[test]
+    fn page_size_is_bounded() {
+        assert_eq!(page_size(None).unwrap(), DEFAULT_PAGE_SIZE);
+        assert_eq!(page_size(Some(MAX_PAGE_SIZE)).unwrap(), MAX_PAGE_SIZE);
+        assert!(page_size(Some(0)).is_err());
+        assert!(page_size(Some(MAX_PAGE_SIZE + 1)).is_err());
+    }
+}
@@ -0,0 +1,394 @@
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, Utc, Weekday};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::AppError;
+
+// A small subset of RFC 5545 RRULE:
+//   FREQ=DAILY;INTERVAL=2
+//   FREQ=WEEKLY;BYDAY=MO,WE,FR
+//   FREQ=MONTHLY;BYMONTHDAY=15
+// Rules are stored in their normalized text form (see Display).
+
+const MAX_INTERVAL: u32 = 999;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    // WEEKLY only; empty means "same weekday as the due date"
+    pub by_day: Vec<Weekday>,
+    // MONTHLY only; None means "same day of month as the due date".
+    // Days past the end of a month fall on its last day.
+    pub by_month_day: Option<u32>,
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    match code {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = AppError;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| {
+            AppError::BadRequest(format!("Invalid recurrence '{}': {}", rule, reason))
+        };
+
+        let rule_body = rule.trim();
+        let rule_body = rule_body
+            .strip_prefix("RRULE:")
+            .unwrap_or(rule_body)
+            .to_ascii_uppercase();
+
+        let mut frequency = None;
+        let mut interval = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+
+        for part in rule_body.split(';').filter(|part| !part.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| invalid("expected KEY=VALUE pairs separated by ';'"))?;
+
+            match key {
+                "FREQ" if frequency.is_none() => {
+                    frequency = Some(match value {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        _ => return Err(invalid("FREQ must be DAILY, WEEKLY or MONTHLY")),
+                    });
+                }
+                "INTERVAL" if interval.is_none() => {
+                    let n = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|n| (1..=MAX_INTERVAL).contains(n))
+                        .ok_or_else(|| invalid("INTERVAL must be between 1 and 999"))?;
+                    interval = Some(n);
+                }
+                "BYDAY" if by_day.is_none() => {
+                    let mut days = value
+                        .split(',')
+                        .map(parse_weekday)
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid("BYDAY takes MO,TU,WE,TH,FR,SA,SU"))?;
+                    days.sort_by_key(|day| day.num_days_from_monday());
+                    days.dedup();
+                    by_day = Some(days);
+                }
+                "BYMONTHDAY" if by_month_day.is_none() => {
+                    let day = value
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|day| (1..=31).contains(day))
+                        .ok_or_else(|| invalid("BYMONTHDAY must be between 1 and 31"))?;
+                    by_month_day = Some(day);
+                }
+                "FREQ" | "INTERVAL" | "BYDAY" | "BYMONTHDAY" => {
+                    return Err(invalid(&format!("{} given more than once", key)));
+                }
+                _ => return Err(invalid(&format!("unsupported part {}", key))),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| invalid("FREQ is required"))?;
+
+        if by_day.is_some() && frequency != Frequency::Weekly {
+            return Err(invalid("BYDAY is only supported with FREQ=WEEKLY"));
+        }
+        if by_month_day.is_some() && frequency != Frequency::Monthly {
+            return Err(invalid("BYMONTHDAY is only supported with FREQ=MONTHLY"));
+        }
+
+        Ok(Recurrence {
+            frequency,
+            interval: interval.unwrap_or(1),
+            by_day: by_day.unwrap_or_default(),
+            by_month_day,
+        })
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+        };
+        write!(f, "FREQ={}", frequency)?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(|day| weekday_code(*day)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+        if let Some(day) = self.by_month_day {
+            write!(f, ";BYMONTHDAY={}", day)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn last_day_of_month(year: i32, month: u32) -> u32 {
+    let first_of_next = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)
+    };
+
+    first_of_next
+        .and_then(|date| date.pred_opt())
+        .map(|date| date.day())
+        .unwrap_or(28)
+}
+
+// Same time of day as `from`, on `day` of from's month (clamped to the month's length)
+fn with_month_day(from: DateTime<Utc>, day: u32) -> DateTime<Utc> {
+    let day = day.min(last_day_of_month(from.year(), from.month()));
+    from.with_day(day).unwrap_or(from)
+}
+
+impl Recurrence {
+    // Monthly rules without BYMONTHDAY take the day from `start`. Pinning it
+    // keeps later occurrences on that day after a short month: Jan 31,
+    // Feb 28, Mar 31 rather than Jan 31, Feb 28, Mar 28.
+    pub fn anchored(&self, start: DateTime<Utc>) -> Recurrence {
+        let mut rule = self.clone();
+        if rule.frequency == Frequency::Monthly && rule.by_month_day.is_none() {
+            rule.by_month_day = Some(start.day());
+        }
+        rule
+    }
+
+    // First occurrence strictly after `from`, keeping its time of day
+    pub fn next_after(&self, from: DateTime<Utc>) -> DateTime<Utc> {
+        let interval = self.interval as i64;
+
+        match self.frequency {
+            Frequency::Daily => from + Duration::days(interval),
+            Frequency::Weekly if self.by_day.is_empty() => from + Duration::weeks(interval),
+            Frequency::Weekly => {
+                let today = from.weekday().num_days_from_monday();
+
+                // A later listed day in the same week...
+                if let Some(day) = self
+                    .by_day
+                    .iter()
+                    .map(|day| day.num_days_from_monday())
+                    .find(|day| *day > today)
+                {
+                    return from + Duration::days((day - today) as i64);
+                }
+
+                // ...otherwise the first listed day, `interval` weeks on
+                let first = self.by_day[0].num_days_from_monday();
+                let week_start = from - Duration::days(today as i64);
+                week_start + Duration::weeks(interval) + Duration::days(first as i64)
+            }
+            Frequency::Monthly => {
+                let target = self.by_month_day.unwrap_or(from.day());
+
+                let this_month = with_month_day(from, target);
+                if this_month > from {
+                    return this_month;
+                }
+
+                let first_of_month = from.with_day(1).unwrap_or(from);
+                let next_month = first_of_month
+                    .checked_add_months(Months::new(self.interval))
+                    .unwrap_or(first_of_month);
+                with_month_day(next_month, target)
+            }
+        }
+    }
+
+    // Next due date for an instance completed at `now`. Occurrences that are
+    // already in the past are skipped, so a chore finished late is not
+    // immediately overdue again.
+    pub fn next_due(&self, due_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> DateTime<Utc> {
+        let start = due_at.unwrap_or(now);
+        let rule = self.anchored(start);
+        let mut next = rule.next_after(start);
+
+        // Bounded so a far-past due date cannot spin for long
+        for _ in 0..10_000 {
+            if next > now {
+                break;
+            }
+            next = rule.next_after(next);
+        }
+
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, 9, 30, 0).unwrap()
+    }
+
+    fn rule(text: &str) -> Recurrence {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn rules_round_trip_through_display() {
+        for text in [
+            "FREQ=DAILY",
+            "FREQ=DAILY;INTERVAL=3",
+            "FREQ=WEEKLY;BYDAY=MO,WE,FR",
+            "FREQ=MONTHLY;INTERVAL=2;BYMONTHDAY=31",
+        ] {
+            assert_eq!(rule(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn rules_are_normalized() {
+        assert_eq!(
+            rule("RRULE:freq=weekly;byday=fr,mo,fr;interval=1").to_string(),
+            "FREQ=WEEKLY;BYDAY=MO,FR"
+        );
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        for text in [
+            "",
+            "INTERVAL=2",
+            "FREQ=YEARLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=1000",
+            "FREQ=DAILY;FREQ=WEEKLY",
+            "FREQ=WEEKLY;BYDAY=XX",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYMONTHDAY=1",
+            "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=DAILY;COUNT=3",
+            "FREQ",
+        ] {
+            assert!(
+                text.parse::<Recurrence>().is_err(),
+                "{:?} was accepted",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn weekly_by_day_wraps_to_the_next_week() {
+        let rule = rule("FREQ=WEEKLY;BYDAY=MO,FR");
+        // 2026-10-14 is a Wednesday
+        assert_eq!(rule.next_after(at(2026, 10, 14)), at(2026, 10, 16));
+        assert_eq!(rule.next_after(at(2026, 10, 16)), at(2026, 10, 19));
+        assert_eq!(rule.next_after(at(2026, 10, 18)), at(2026, 10, 19));
+    }
+
+    #[test]
+    fn interval_skips_periods() {
+        assert_eq!(
+            rule("FREQ=DAILY;INTERVAL=3").next_after(at(2026, 10, 30)),
+            at(2026, 11, 2)
+        );
+        assert_eq!(
+            rule("FREQ=WEEKLY;INTERVAL=2").next_after(at(2026, 10, 14)),
+            at(2026, 10, 28)
+        );
+        // After Friday the next listed day is Monday two weeks on
+        assert_eq!(
+            rule("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR").next_after(at(2026, 10, 16)),
+            at(2026, 10, 26)
+        );
+        assert_eq!(
+            rule("FREQ=MONTHLY;INTERVAL=3;BYMONTHDAY=15").next_after(at(2026, 10, 15)),
+            at(2027, 1, 15)
+        );
+    }
+
+    #[test]
+    fn month_end_is_clamped_without_drifting() {
+        // Each completion stores the anchored rule on the next instance, as
+        // database::update_todo does
+        let mut series = rule("FREQ=MONTHLY");
+        let mut due = at(2027, 1, 31);
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            series = series.anchored(due);
+            due = series.next_due(Some(due), due);
+            seen.push(due);
+        }
+        assert_eq!(
+            seen,
+            vec![at(2027, 2, 28), at(2027, 3, 31), at(2027, 4, 30)]
+        );
+
+        // Leap years land on the 29th
+        assert_eq!(
+            rule("FREQ=MONTHLY").next_due(Some(at(2028, 1, 31)), at(2028, 1, 31)),
+            at(2028, 2, 29)
+        );
+    }
+
+    #[test]
+    fn next_due_skips_past_occurrences_without_drifting() {
+        // Completed in mid-April: Feb 28 and Mar 31 have passed
+        let next = rule("FREQ=MONTHLY").next_due(Some(at(2027, 1, 31)), at(2027, 4, 10));
+        assert_eq!(next, at(2027, 4, 30));
+    }
+
+    #[test]
+    fn anchored_pins_only_monthly_rules_without_a_day() {
+        assert_eq!(
+            rule("FREQ=MONTHLY").anchored(at(2027, 1, 31)).to_string(),
+            "FREQ=MONTHLY;BYMONTHDAY=31"
+        );
+        assert_eq!(
+            rule("FREQ=MONTHLY;BYMONTHDAY=5")
+                .anchored(at(2027, 1, 31))
+                .to_string(),
+            "FREQ=MONTHLY;BYMONTHDAY=5"
+        );
+        assert_eq!(
+            rule("FREQ=DAILY").anchored(at(2027, 1, 31)).to_string(),
+            "FREQ=DAILY"
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use actix_web::rt;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::cache::ListCache;
+use crate::config::RetentionSettings;
+use crate::database;
+
+// Background task that archives old completed todos and, when configured,
+// deletes todos that have been archived for long enough. Runs on the actix
+// runtime for the lifetime of the server.
+pub fn spawn_retention_job(pool: PgPool, settings: RetentionSettings, cache: Arc<ListCache>) {
+    rt::spawn(async move {
+        let mut ticker = rt::time::interval(settings.interval);
+
+        loop {
+            ticker.tick().await;
+            if run_retention(&pool, &settings).await {
+                cache.invalidate();
+            }
+        }
+    });
+}
+
+fn cutoff(age: Duration) -> chrono::DateTime<Utc> {
+    // Ages beyond chrono's range mean nothing is old enough
+    chrono::Duration::from_std(age)
+        .ok()
+        .and_then(|age| Utc::now().checked_sub_signed(age))
+        .unwrap_or(chrono::DateTime::<Utc>::MIN_UTC)
+}
+
+// Returns whether any todo was changed
+async fn run_retention(pool: &PgPool, settings: &RetentionSettings) -> bool {
+    let mut changed = false;
+
+    match database::archive_completed_todos(pool, cutoff(settings.archive_after)).await {
+        Ok(ids) => {
+            changed |= !ids.is_empty();
+            log::info!("Retention: archived {} completed todo(s)", ids.len());
+            if !ids.is_empty() {
+                log::debug!("Retention: archived todos {:?}", ids);
+            }
+        }
+        Err(e) => log::error!("Retention archive failed: {}", e),
+    }
+
+    let Some(delete_after) = settings.delete_after else {
+        return changed;
+    };
+    match database::delete_archived_todos(pool, cutoff(delete_after)).await {
+        Ok(ids) => {
+            changed |= !ids.is_empty();
+            log::info!("Retention: deleted {} archived todo(s)", ids.len());
+            if !ids.is_empty() {
+                log::debug!("Retention: deleted todos {:?}", ids);
+            }
+        }
+        Err(e) => log::error!("Retention delete failed: {}", e),
+    }
+
+    changed
+}
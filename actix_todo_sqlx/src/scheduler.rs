@@ -0,0 +1,53 @@
+use actix_web::rt;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::config::ReminderSettings;
+use crate::database;
+use crate::models::ReminderEvent;
+use crate::notifier::Notifier;
+
+// Background task that periodically emits reminders for todos coming due.
+// Runs on the actix runtime for the lifetime of the server.
+pub fn spawn_reminder_scheduler(
+    pool: PgPool,
+    settings: ReminderSettings,
+    notifier: Arc<dyn Notifier>,
+) {
+    rt::spawn(async move {
+        let mut ticker = rt::time::interval(settings.interval);
+
+        loop {
+            ticker.tick().await;
+            run_reminders(&pool, &settings, notifier.as_ref()).await;
+        }
+    });
+}
+
+async fn run_reminders(pool: &PgPool, settings: &ReminderSettings, notifier: &dyn Notifier) {
+    let todos = match database::claim_due_reminders(pool, settings.lead_time).await {
+        Ok(todos) => todos,
+        Err(e) => {
+            log::error!("Reminder scan failed: {}", e);
+            return;
+        }
+    };
+
+    for todo in &todos {
+        let Some(event) = ReminderEvent::due_soon(todo) else {
+            continue;
+        };
+
+        if let Err(e) = notifier.notify(&event).await {
+            log::warn!("Reminder for todo {} not delivered: {}", event.todo_id, e);
+
+            if let Err(e) = database::release_reminder(pool, event.todo_id).await {
+                log::error!(
+                    "Could not re-arm reminder for todo {}: {}",
+                    event.todo_id,
+                    e
+                );
+            }
+        }
+    }
+}
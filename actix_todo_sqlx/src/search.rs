@@ -0,0 +1,210 @@
+use crate::errors::AppError;
+
+// Search syntax for GET /todos/search:
+//   groceries milk      both words (stemmed, so "groceries" also finds "grocery")
+//   "weekly report"     the words next to each other, in this order
+//   deploy*             any word starting with "deploy"
+//   -draft              leave out todos containing the word
+// Terms are combined with AND.
+
+const MAX_QUERY_LEN: usize = 256;
+const MAX_TERMS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TermKind {
+    Word,
+    Prefix,
+    Phrase,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub kind: TermKind,
+    pub text: String,
+    pub negated: bool,
+}
+
+pub fn parse(query: &str) -> Result<Vec<Term>, AppError> {
+    if query.len() > MAX_QUERY_LEN {
+        return Err(AppError::BadRequest(format!(
+            "Search query cannot be longer than {} characters",
+            MAX_QUERY_LEN
+        )));
+    }
+
+    let mut terms = Vec::new();
+    let mut chars = query.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        let negated = c == '-';
+        if negated {
+            chars.next();
+        }
+
+        let term = if chars.peek() == Some(&'"') {
+            chars.next();
+            // An unterminated quote runs to the end of the query
+            let text: String = chars.by_ref().take_while(|c| *c != '"').collect();
+            Term {
+                kind: TermKind::Phrase,
+                text: text.trim().to_string(),
+                negated,
+            }
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+
+            match word.strip_suffix('*') {
+                // Prefixes go into to_tsquery, so only letters and digits are kept
+                Some(prefix) => Term {
+                    kind: TermKind::Prefix,
+                    text: prefix.chars().filter(|c| c.is_alphanumeric()).collect(),
+                    negated,
+                },
+                None => Term {
+                    kind: TermKind::Word,
+                    text: word,
+                    negated,
+                },
+            }
+        };
+
+        if !term.text.is_empty() {
+            terms.push(term);
+        }
+    }
+
+    if terms.is_empty() {
+        return Err(AppError::BadRequest(
+            "Search query cannot be empty".to_string(),
+        ));
+    }
+    if terms.len() > MAX_TERMS {
+        return Err(AppError::BadRequest(format!(
+            "Search query can have at most {} terms",
+            MAX_TERMS
+        )));
+    }
+
+    Ok(terms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn term(kind: TermKind, text: &str, negated: bool) -> Term {
+        Term {
+            kind,
+            text: text.to_string(),
+            negated,
+        }
+    }
+
+    #[test]
+    fn words_are_split_on_whitespace() {
+        assert_eq!(
+            parse("  groceries\tmilk ").unwrap(),
+            vec![
+                term(TermKind::Word, "groceries", false),
+                term(TermKind::Word, "milk", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn quotes_make_a_phrase() {
+        assert_eq!(
+            parse(r#"send "weekly  report " -"old draft""#).unwrap(),
+            vec![
+                term(TermKind::Word, "send", false),
+                term(TermKind::Phrase, "weekly  report", false),
+                term(TermKind::Phrase, "old draft", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_quote_runs_to_the_end() {
+        assert_eq!(
+            parse(r#"a "b c"#).unwrap(),
+            vec![
+                term(TermKind::Word, "a", false),
+                term(TermKind::Phrase, "b c", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_star_makes_a_prefix() {
+        assert_eq!(
+            parse("deploy* -re-run* ok*!").unwrap(),
+            vec![
+                term(TermKind::Prefix, "deploy", false),
+                term(TermKind::Prefix, "rerun", true),
+                term(TermKind::Word, "ok*!", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn prefix_is_reduced_to_letters_and_digits() {
+        assert_eq!(
+            parse("a:*|b&!c*").unwrap(),
+            vec![term(TermKind::Prefix, "abc", false)]
+        );
+    }
+
+    #[test]
+    fn minus_negates_the_term() {
+        assert_eq!(
+            parse("report -draft").unwrap(),
+            vec![
+                term(TermKind::Word, "report", false),
+                term(TermKind::Word, "draft", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn only_negated_terms_are_allowed() {
+        assert_eq!(
+            parse("-draft -\"to do\"").unwrap(),
+            vec![
+                term(TermKind::Word, "draft", true),
+                term(TermKind::Phrase, "to do", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_queries_are_rejected() {
+        for query in ["", "   ", "-", "\"\"", "- \"  \"", "*", "-*"] {
+            assert!(
+                matches!(parse(query), Err(AppError::BadRequest(_))),
+                "{:?} was accepted",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn long_queries_are_rejected() {
+        assert!(parse(&"a".repeat(MAX_QUERY_LEN)).is_ok());
+        assert!(parse(&"a".repeat(MAX_QUERY_LEN + 1)).is_err());
+
+        let terms = vec!["word"; MAX_TERMS + 1].join(" ");
+        assert!(parse(&terms).is_err());
+    }
+}
@@ -0,0 +1,466 @@
+use actix_web::rt;
+use chrono::Utc;
+use futures_util::future::join_all;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use reqwest::Url;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use sha2::Sha256;
+use sqlx::PgPool;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::WebhookSettings;
+use crate::database::{self, PendingDelivery};
+use crate::errors::AppError;
+
+// Events a webhook can subscribe to. Completing a todo sends todo.completed
+// instead of todo.updated.
+pub const EVENTS: [&str; 4] = [
+    "todo.created",
+    "todo.updated",
+    "todo.completed",
+    "todo.deleted",
+];
+
+const BATCH_SIZE: i64 = 20;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+// Longer than a request can take, so a claimed delivery is only picked up
+// again if the worker died before recording the outcome
+const CLAIM_LEASE: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+// Loopback, private, link-local (cloud metadata at 169.254.169.254) and
+// similar addresses. Webhooks may not reach them unless
+// webhooks.allow_private_targets is set, so a user cannot make the server
+// call into its own network.
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_v4(ip),
+            None => is_private_v6(ip),
+        },
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || a == 0
+        // Carrier-grade NAT, 100.64.0.0/10
+        || (a == 100 && (b & 0xc0) == 64)
+        // Benchmarking, 198.18.0.0/15
+        || (a == 198 && (b & 0xfe) == 18)
+}
+
+// Ranges that embed an IPv4 address are refused outright rather than
+// checked by the address they embed, since a relay or gateway decides
+// where they end up
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    let segments = ip.segments();
+    let first = segments[0];
+    ip.is_loopback()
+        || ip.is_unspecified()
+        // IPv4-compatible, ::a.b.c.d
+        || segments[..6] == [0; 6]
+        // NAT64, 64:ff9b::/96 and the local-use 64:ff9b:1::/48
+        || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+        || segments[..3] == [0x64, 0xff9b, 1]
+        // 6to4, 2002::/16
+        || first == 0x2002
+        // Unique local, fc00::/7
+        || (first & 0xfe00) == 0xfc00
+        // Link-local, fe80::/10
+        || (first & 0xffc0) == 0xfe80
+}
+
+// The host of `url` when it is an IP address. Url::parse has already
+// normalized forms such as http://2130706433/ to dotted decimal.
+fn literal_ip(url: &Url) -> Option<IpAddr> {
+    let host = url.host_str()?;
+    host.trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .ok()
+}
+
+pub async fn validate_url(url: &str, allow_private: bool) -> Result<(), AppError> {
+    let parsed = Url::parse(url)
+        .map_err(|e| AppError::BadRequest(format!("Invalid webhook url '{}': {}", url, e)))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AppError::BadRequest(
+            "Webhook url must use http or https".to_string(),
+        ));
+    }
+    if allow_private {
+        return Ok(());
+    }
+
+    let private = || {
+        AppError::BadRequest(format!(
+            "Webhook url '{}' points to a loopback, private or link-local address",
+            url
+        ))
+    };
+    if let Some(ip) = literal_ip(&parsed) {
+        return if is_private(ip) {
+            Err(private())
+        } else {
+            Ok(())
+        };
+    }
+
+    // A name that does not resolve yet is accepted; the worker checks the
+    // addresses again on every delivery
+    let (Some(host), Some(port)) = (parsed.host_str(), parsed.port_or_known_default()) else {
+        return Ok(());
+    };
+    if let Ok(mut addrs) = tokio::net::lookup_host((host, port)).await
+        && addrs.any(|addr| is_private(addr.ip()))
+    {
+        return Err(private());
+    }
+
+    Ok(())
+}
+
+// Leaves private addresses out of every name the delivery client looks up,
+// so a host name cannot be pointed at one after the webhook was saved
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_string();
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|addr| !is_private(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(format!("{} has no public address", host).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+pub fn validate_events(events: &[String]) -> Result<(), AppError> {
+    if events.is_empty() {
+        return Err(AppError::BadRequest("events cannot be empty".to_string()));
+    }
+    if let Some(unknown) = events
+        .iter()
+        .find(|event| !EVENTS.contains(&event.as_str()))
+    {
+        return Err(AppError::BadRequest(format!(
+            "Unknown event '{}' (expected {})",
+            unknown,
+            EVENTS.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+pub fn new_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+// Value of the X-Webhook-Signature header: the HMAC-SHA256 of
+// "<timestamp>.<body>" keyed with the webhook's secret. Including the
+// timestamp lets receivers reject old requests that are replayed.
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+// Delay before the next attempt after `attempts` failed ones
+fn retry_delay(settings: &WebhookSettings, attempts: i32) -> Duration {
+    let doublings = (attempts - 1).clamp(0, 20) as u32;
+    settings
+        .retry_base
+        .saturating_mul(2u32.pow(doublings))
+        .min(MAX_RETRY_DELAY)
+}
+
+// Background task that sends queued deliveries. Runs on the actix runtime
+// for the lifetime of the server.
+pub fn spawn_webhook_worker(pool: PgPool, settings: WebhookSettings) {
+    let mut builder = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        // A redirect counts as a failed delivery rather than being followed
+        .redirect(reqwest::redirect::Policy::none())
+        .user_agent("actix_todo_sqlx-webhooks");
+    if !settings.allow_private_targets {
+        builder = builder.dns_resolver(Arc::new(PublicResolver));
+    }
+    let client = builder.build().expect("Failed to build HTTP client");
+
+    rt::spawn(async move {
+        let mut ticker = rt::time::interval(settings.interval);
+
+        loop {
+            ticker.tick().await;
+            // Keep going while full batches come back, so a backlog drains
+            // without waiting for the next tick
+            while run_deliveries(&pool, &settings, &client).await == BATCH_SIZE as usize {}
+        }
+    });
+}
+
+async fn run_deliveries(
+    pool: &PgPool,
+    settings: &WebhookSettings,
+    client: &reqwest::Client,
+) -> usize {
+    let deliveries = match database::claim_deliveries(pool, BATCH_SIZE, CLAIM_LEASE).await {
+        Ok(deliveries) => deliveries,
+        Err(e) => {
+            log::error!("Webhook scan failed: {}", e);
+            return 0;
+        }
+    };
+
+    join_all(
+        deliveries
+            .iter()
+            .map(|delivery| deliver(pool, settings, client, delivery)),
+    )
+    .await;
+
+    deliveries.len()
+}
+
+// reqwest's errors only name the url; the causes (connection refused, no
+// public address) are in the source chain
+fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    message
+}
+
+async fn deliver(
+    pool: &PgPool,
+    settings: &WebhookSettings,
+    client: &reqwest::Client,
+    delivery: &PendingDelivery,
+) {
+    let body = delivery.payload.to_string();
+    let timestamp = Utc::now().timestamp();
+
+    // IP addresses in the url never reach the resolver, so they are checked
+    // here; the url may also predate a change to allow_private_targets
+    let blocked = !settings.allow_private_targets
+        && Url::parse(&delivery.url)
+            .ok()
+            .and_then(|url| literal_ip(&url))
+            .is_some_and(is_private);
+
+    let outcome = if blocked {
+        Err("Url points to a loopback, private or link-local address".to_string())
+    } else {
+        client
+            .post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("X-Webhook-Event", &delivery.event)
+            .header("X-Webhook-Delivery", delivery.id.to_string())
+            .header(
+                "X-Webhook-Signature",
+                signature(&delivery.secret, timestamp, body.as_bytes()),
+            )
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| format!("Request failed: {}", error_chain(&e)))
+    };
+
+    let result = match outcome {
+        Ok(response) if response.status().is_success() => {
+            database::mark_delivered(pool, delivery.id, response.status().as_u16() as i32).await
+        }
+        outcome => {
+            let (error, response_status) = match outcome {
+                Ok(response) => (
+                    format!("Receiver returned {}", response.status()),
+                    Some(response.status().as_u16() as i32),
+                ),
+                Err(error) => (error, None),
+            };
+
+            let attempts = delivery.attempts + 1;
+            let retry_at = (attempts < settings.max_attempts).then(|| {
+                Utc::now()
+                    + chrono::Duration::from_std(retry_delay(settings, attempts))
+                        .unwrap_or_else(|_| chrono::Duration::hours(6))
+            });
+
+            match retry_at {
+                Some(retry_at) => log::warn!(
+                    "Webhook delivery {} failed (attempt {}): {}; retrying at {}",
+                    delivery.id,
+                    attempts,
+                    error,
+                    retry_at
+                ),
+                None => log::warn!(
+                    "Webhook delivery {} failed (attempt {}): {}; giving up",
+                    delivery.id,
+                    attempts,
+                    error
+                ),
+            }
+
+            database::mark_failed(pool, delivery.id, &error, response_status, retry_at).await
+        }
+    };
+
+    if let Err(e) = result {
+        log::error!(
+            "Could not record the outcome of webhook delivery {}: {}",
+            delivery.id,
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn webhook_settings(retry_base_secs: u64) -> WebhookSettings {
+        WebhookSettings {
+            enabled: true,
+            interval: Duration::from_secs(5),
+            max_attempts: 8,
+            retry_base: Duration::from_secs(retry_base_secs),
+            allow_private_targets: false,
+        }
+    }
+
+    #[test]
+    fn signature_matches_a_known_value() {
+        // python3 -c 'import hmac, hashlib; print(hmac.new(b"whsec_test",
+        //   b"1700000000.{\"event\":\"todo.created\"}", hashlib.sha256).hexdigest())'
+        assert_eq!(
+            signature("whsec_test", 1_700_000_000, br#"{"event":"todo.created"}"#),
+            "t=1700000000,v1=0db4dcd7e7c5bc797d5b40bad35628d1b595ae10063a537e47ef00ffc5b800c9"
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_secret_timestamp_and_body() {
+        let base = signature("a", 1, b"{}");
+        assert_ne!(base, signature("b", 1, b"{}"));
+        assert_ne!(base, signature("a", 2, b"{}"));
+        assert_ne!(base, signature("a", 1, b"{ }"));
+    }
+
+    #[test]
+    fn retry_delay_doubles_from_the_base() {
+        let settings = webhook_settings(30);
+        let delays: Vec<u64> = (1..=4)
+            .map(|attempts| retry_delay(&settings, attempts).as_secs())
+            .collect();
+        assert_eq!(delays, vec![30, 60, 120, 240]);
+        assert_eq!(retry_delay(&settings, 0), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn retry_delay_is_clamped() {
+        let settings = webhook_settings(30);
+        assert_eq!(retry_delay(&settings, 12), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(&settings, i32::MAX), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(&webhook_settings(u64::MAX), 3), MAX_RETRY_DELAY);
+    }
+
+    #[test]
+    fn private_addresses_are_recognized() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::127.0.0.1",
+            "::8.8.8.8",
+            "64:ff9b::a9fe:a9fe",
+            "64:ff9b:1::1",
+            "2002:7f00:1::1",
+            "198.18.0.1",
+            "198.19.255.255",
+        ] {
+            assert!(is_private(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in [
+            "93.184.216.34",
+            "8.8.8.8",
+            "2606:4700::1111",
+            "172.32.0.1",
+            "198.20.0.1",
+            "::ffff:8.8.8.8",
+            "64:ff9c::1",
+        ] {
+            assert!(!is_private(ip.parse().unwrap()), "{}", ip);
+        }
+    }
+
+    #[actix_web::test]
+    async fn private_urls_are_rejected_unless_allowed() {
+        for url in [
+            "http://127.0.0.1:9000/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://2130706433/hook",
+            "http://localhost:9000/hook",
+        ] {
+            assert!(
+                matches!(validate_url(url, false).await, Err(AppError::BadRequest(_))),
+                "{} was accepted",
+                url
+            );
+            assert!(validate_url(url, true).await.is_ok(), "{}", url);
+        }
+
+        assert!(
+            validate_url("https://93.184.216.34/hook", false)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[actix_web::test]
+    async fn only_http_urls_are_accepted() {
+        assert!(validate_url("ftp://93.184.216.34/", true).await.is_err());
+        assert!(validate_url("not a url", true).await.is_err());
+    }
+}
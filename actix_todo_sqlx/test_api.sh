@@ -4,11 +4,23 @@ echo "=== Testing Todo API ==="
 echo
 
 echo "1. Health Check:"
-curl -s http://127.0.0.1:8080/health
+curl -s http://127.0.0.1:8080/health/ready
 echo -e "\n"
 
-echo "2. Create Todo 1:"
-curl -s -X POST http://127.0.0.1:8080/api/v1/todos \
+echo "2. Register (or log in) and get a token:"
+CREDENTIALS='{"email": "demo@example.com", "password": "demo-password"}'
+TOKEN=$(curl -s -X POST http://127.0.0.1:8080/api/v1/auth/register \
+  -H "Content-Type: application/json" -d "$CREDENTIALS" | jq -r '.token // empty')
+if [ -z "$TOKEN" ]; then
+  TOKEN=$(curl -s -X POST http://127.0.0.1:8080/api/v1/auth/login \
+    -H "Content-Type: application/json" -d "$CREDENTIALS" | jq -r '.token')
+fi
+AUTH="Authorization: Bearer $TOKEN"
+echo "$TOKEN"
+echo
+
+echo "3. Create Todo 1:"
+curl -s -X POST -H "$AUTH" http://127.0.0.1:8080/api/v1/todos \
   -H "Content-Type: application/json" \
   -d '{
     "title": "Learn Rust",
@@ -17,8 +29,8 @@ curl -s -X POST http://127.0.0.1:8080/api/v1/todos \
   }' | jq '.'
 echo
 
-echo "3. Create Todo 2:"
-curl -s -X POST http://127.0.0.1:8080/api/v1/todos \
+echo "4. Create Todo 2:"
+curl -s -X POST -H "$AUTH" http://127.0.0.1:8080/api/v1/todos \
   -H "Content-Type: application/json" \
   -d '{
     "title": "Buy groceries",
@@ -27,16 +39,16 @@ curl -s -X POST http://127.0.0.1:8080/api/v1/todos \
   }' | jq '.'
 echo
 
-echo "4. List all todos:"
-curl -s http://127.0.0.1:8080/api/v1/todos | jq '.'
+echo "5. List all todos:"
+curl -s -H "$AUTH" http://127.0.0.1:8080/api/v1/todos | jq '.'
 echo
 
-echo "5. Get specific todo (ID 1):"
-curl -s http://127.0.0.1:8080/api/v1/todos/1 | jq '.'
+echo "6. Get specific todo (ID 1):"
+curl -s -H "$AUTH" http://127.0.0.1:8080/api/v1/todos/1 | jq '.'
 echo
 
-echo "6. Update todo (mark as completed):"
-curl -s -X PUT http://127.0.0.1:8080/api/v1/todos/1 \
+echo "7. Update todo (mark as completed):"
+curl -s -X PATCH -H "$AUTH" http://127.0.0.1:8080/api/v1/todos/1 \
   -H "Content-Type: application/json" \
   -d '{
     "completed": true,
@@ -44,21 +56,21 @@ curl -s -X PUT http://127.0.0.1:8080/api/v1/todos/1 \
   }' | jq '.'
 echo
 
-echo "7. List all todos after update:"
-curl -s http://127.0.0.1:8080/api/v1/todos | jq '.'
+echo "8. List all todos after update:"
+curl -s -H "$AUTH" http://127.0.0.1:8080/api/v1/todos | jq '.'
 echo
 
-echo "8. Delete todo (ID 2):"
-curl -s -X DELETE http://127.0.0.1:8080/api/v1/todos/2
+echo "9. Delete todo (ID 2):"
+curl -s -X DELETE -H "$AUTH" http://127.0.0.1:8080/api/v1/todos/2
 echo "Status: $?"
 echo
 
-echo "9. List all todos after delete:"
-curl -s http://127.0.0.1:8080/api/v1/todos | jq '.'
+echo "10. List all todos after delete:"
+curl -s -H "$AUTH" http://127.0.0.1:8080/api/v1/todos | jq '.'
 echo
 
-echo "10. Try to get deleted todo (should return 404):"
-curl -s http://127.0.0.1:8080/api/v1/todos/2
+echo "11. Try to get deleted todo (should return 404):"
+curl -s -H "$AUTH" http://127.0.0.1:8080/api/v1/todos/2
 echo -e "\n"
 
 echo "=== Testing Complete ==="